@@ -3,6 +3,7 @@ use std::{
     sync::{
         atomic::{
             AtomicBool,
+            AtomicUsize,
             Ordering
         },
         Arc
@@ -11,64 +12,153 @@ use std::{
 
 use tracing::info;
 use anyhow::Result;
-use crate::message::{AIMessage, BaseMessage, Message, MessageFrame, UserMessage};
+use crate::client::ChatInfo;
+use crate::message::{self, AIMessage, BaseMessage, Message, MessageFrame, ToolInvocation, UserMessage};
 use tokio::sync::{Mutex};
 
+/// Local handle of a conversation, stable before the host assigns a chat id.
+pub type ThreadKey = usize;
+
+static NEXT_THREAD_KEY: AtomicUsize = AtomicUsize::new(0);
+
 #[derive(Debug, Clone)]
 pub struct ChatThread {
+    key: ThreadKey,
     inner: Arc<Mutex<ChatThreadInner>>,
 }
 
 impl Default for ChatThread {
     fn default() -> Self {
         Self {
+            key: NEXT_THREAD_KEY.fetch_add(1, Ordering::Relaxed),
             inner: Arc::new(Mutex::new(ChatThreadInner::default())),
         }
     }
 }
 
 impl ChatThread {
+    #[inline]
+    pub fn key(&self) -> ThreadKey {
+        self.key
+    }
+}
+
+impl Deref for ChatThread {
+    type Target = Mutex<ChatThreadInner>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.inner
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct ChatThreadInner {
+    pub id: Option<String>,
+    pub title: Option<String>,
+    pub created_at: Option<String>,
+    pub messages: Vec<Arc<MessageFrame>>,
+}
+
+impl ChatThreadInner {
+    /// How the host lists this conversation, `None` before it has an id.
+    pub fn info(&self) -> Option<ChatInfo> {
+        Some(ChatInfo {
+            id: self.id.clone()?,
+            title: self.title.clone().unwrap_or_default(),
+            created_at: self.created_at.clone(),
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ChatSummary {
+    pub key: ThreadKey,
+    pub title: Option<String>,
+}
+
+/// Every conversation open in this session, in the order they were opened.
+#[derive(Debug, Clone, Default)]
+pub struct ChatRegistry {
+    threads: Arc<Mutex<Vec<ChatThread>>>,
+}
+
+impl ChatRegistry {
     pub fn split(self) -> (ChatWriter, ChatReader) {
         let update_flag = Arc::new(AtomicBool::new(false));
         (
             ChatWriter {
+                registry: self.clone(),
                 update_flag: update_flag.clone(),
-                thread: self.inner.clone(),
+                thread: None,
                 user_message: None,
                 ai_message: None,
-                thread_id: None,
+                tools: vec![],
+                interrupted: false,
+                error: None,
+                retry_of: None,
+                truncated: vec![],
             },
             ChatReader {
-                thread: self.inner.clone(),
-                thread_id: None,
+                registry: self,
+                key: None,
                 messages: vec![],
                 update_flag: update_flag.clone(),
             }
         )
     }
-}
 
-impl Deref for ChatThread {
-    type Target = Mutex<ChatThreadInner>;
+    pub async fn create(&self) -> ThreadKey {
+        let thread = ChatThread::default();
+        let key = thread.key();
+        self.threads.lock().await.push(thread);
+        key
+    }
 
-    fn deref(&self) -> &Self::Target {
-        &self.inner
+    pub async fn get(&self, key: ThreadKey) -> Option<ChatThread> {
+        self.threads.lock().await.iter().find(|t| t.key() == key).cloned()
     }
-}
 
-#[derive(Debug, Default)]
-pub struct ChatThreadInner {
-    pub id: Option<String>,
-    pub messages: Vec<Arc<MessageFrame>>,
+    /// Find the thread holding the host chat `id`.
+    pub async fn find(&self, id: &str) -> Option<ChatThread> {
+        let threads = self.threads.lock().await.clone();
+        for thread in threads {
+            if thread.lock().await.id.as_deref() == Some(id) {
+                return Some(thread);
+            }
+        }
+
+        None
+    }
+
+    pub async fn summaries(&self) -> Vec<ChatSummary> {
+        let threads = self.threads.lock().await.clone();
+        let mut summaries = Vec::with_capacity(threads.len());
+        for thread in threads {
+            let inner = thread.lock().await;
+            summaries.push(ChatSummary {
+                key: thread.key(),
+                title: inner.title.clone(),
+            });
+        }
+
+        summaries
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct ChatWriter {
-    thread: Arc<Mutex<ChatThreadInner>>,
+    registry: ChatRegistry,
+    thread: Option<ChatThread>,
     update_flag: Arc<AtomicBool>,
     pub user_message: Option<BaseMessage>,
     pub ai_message: Option<BaseMessage>,
-    pub thread_id: Option<String>,
+    pub tools: Vec<ToolInvocation>,
+    pub interrupted: bool,
+    pub error: Option<String>,
+    /// The turn being generated again, kept until the new answer is flushed.
+    retry_of: Option<Arc<MessageFrame>>,
+    /// Turns dropped by a retry or an edit, put back if no answer comes.
+    truncated: Vec<Arc<MessageFrame>>,
 }
 
 impl ChatWriter {
@@ -82,25 +172,177 @@ impl ChatWriter {
         self.ai_message.get_or_insert_default()
     }
 
+    pub fn push_tool_call(&mut self, name: String, arguments: String) {
+        let offset = self.mut_ai_message().content.len();
+        self.tools.push(ToolInvocation { name, arguments, result: None, offset });
+    }
+
+    pub fn set_tool_result(&mut self, name: &str, result: String) {
+        ToolInvocation::resolve(&mut self.tools, name, result);
+    }
+
+    /// Mark the pending ai message as cut short by the user.
+    pub fn interrupt(&mut self) {
+        self.mut_ai_message();
+        self.interrupted = true;
+    }
+
+    /// Record why the pending ai message could not be completed.
+    pub fn fail(&mut self, error: String) {
+        self.mut_ai_message();
+        self.error = Some(error);
+    }
+
+    /// Direct the next messages to the thread `key`, returning its chat id.
+    pub async fn select(&mut self, key: ThreadKey) -> Result<Option<String>> {
+        let thread = self.registry.get(key).await
+            .ok_or_else(|| anyhow::anyhow!("unknown chat thread {}", key))?;
+        let id = thread.lock().await.id.clone();
+
+        self.user_message = None;
+        self.ai_message = None;
+        self.tools.clear();
+        self.interrupted = false;
+        self.error = None;
+        self.retry_of = None;
+        self.truncated.clear();
+        self.thread = Some(thread);
+        Ok(id)
+    }
+
+    /// Take the turn whose ai message is `message_id` out of the thread `key`
+    /// to generate its answer again, returning the chat id.
+    ///
+    /// Later turns are dropped, as the host does when retrying.
+    pub async fn retry(&mut self, key: ThreadKey, message_id: &str) -> Result<String> {
+        let (id, frame) = self.truncate(key, |frame| frame.ai.body.message_id == message_id).await?;
+
+        self.user_message = Some(frame.user.body.clone());
+        self.retry_of = Some(frame);
+        Ok(id)
+    }
+
+    /// Replace the user message `message_id` of the thread `key` by `content`,
+    /// returning the chat id. The turn and every later one are dropped, the
+    /// answer to the new content starts another branch.
+    pub async fn edit(&mut self, key: ThreadKey, message_id: &str, content: String) -> Result<String> {
+        let (id, frame) = self.truncate(key, |frame| frame.user.body.message_id == message_id).await?;
+
+        let user_message = self.user_message.insert(frame.user.body.clone());
+        user_message.content = content;
+        Ok(id)
+    }
+
+    /// Select the thread `key` and drop its first turn matching `is_turn` and
+    /// all after it, returning the chat id and that turn. The dropped turns
+    /// are kept until the next flush.
+    async fn truncate(&mut self, key: ThreadKey, is_turn: impl Fn(&MessageFrame) -> bool) -> Result<(String, Arc<MessageFrame>)> {
+        let id = self.select(key).await?
+            .ok_or_else(|| anyhow::anyhow!("chat thread {} is not known to the host", key))?;
+        let thread = self.registry.get(key).await
+            .ok_or_else(|| anyhow::anyhow!("unknown chat thread {}", key))?;
+        let mut thread = thread.lock().await;
+
+        let index = thread.messages.iter()
+            .position(|frame| is_turn(frame))
+            .ok_or_else(|| anyhow::anyhow!("no such message in chat {}", id))?;
+        self.truncated = thread.messages.split_off(index);
+        let frame = self.truncated[0].clone();
+
+        self.update_flag.store(true, Ordering::Release);
+        Ok((id, frame))
+    }
+
+    /// Record the host chat id, title and creation date of the selected thread.
+    pub async fn set_info(&mut self, info: ChatInfo) {
+        let Some(thread) = &self.thread else {
+            return;
+        };
+
+        let mut thread = thread.lock().await;
+        thread.id = Some(info.id);
+        thread.title = Some(info.title);
+        if info.created_at.is_some() {
+            thread.created_at = info.created_at;
+        }
+        self.update_flag.store(true, Ordering::Release);
+    }
+
+    /// The selected thread.
+    #[inline]
+    pub fn thread(&self) -> Option<&ChatThread> {
+        self.thread.as_ref()
+    }
+
+    /// Replace the thread `key` with a conversation fetched from the host.
+    pub async fn load(&mut self, key: ThreadKey, info: ChatInfo, frames: Vec<MessageFrame>) -> Result<()> {
+        self.select(key).await?;
+        self.set_info(info).await;
+
+        if let Some(thread) = &self.thread {
+            thread.lock().await.messages = frames.into_iter().map(Arc::new).collect();
+            self.update_flag.store(true, Ordering::Release);
+        }
+
+        Ok(())
+    }
+
     pub async fn flush(&mut self) -> Result<Arc<MessageFrame>> {
-        let mut thread = self.thread.lock().await;
+        let thread = self.thread.clone().ok_or_else(|| anyhow::anyhow!("no chat thread selected"))?;
+        let mut thread = thread.lock().await;
 
-        thread.id = self.thread_id.take();
+        let tools = std::mem::take(&mut self.tools);
+        let interrupted = std::mem::take(&mut self.interrupted);
+        let error = self.error.take();
+        let retry_of = self.retry_of.take();
+        let truncated = std::mem::take(&mut self.truncated);
+        let tool_calls = tools.iter()
+            .map(|tool| message::ToolCall {
+                name: tool.name.clone(),
+                id: String::new(),
+                args: serde_json::from_str(&tool.arguments).unwrap_or_else(|_| tool.arguments.clone().into()),
+            })
+            .collect();
 
         let user_message: Option<UserMessage> = self.user_message.take()
             .map(|msg| Message::UserMessage(UserMessage { body: msg }))
             .and_then(|msg| msg.try_into().ok());
         let ai_message: Option<AIMessage> = self.ai_message.take()
-            .map(|msg| Message::AIMessage(AIMessage { body: msg, tool_calls: vec![], files: vec![] }))
+            .map(|msg| Message::AIMessage(AIMessage { body: msg, tool_calls, files: vec![] }))
             .and_then(|msg| msg.try_into().ok());
 
-        let (Some(user_message), Some(ai_message)) = (user_message.clone(), ai_message.clone()) else {
+        // a retry keeps the answer it replaces among the alternates, an edit
+        // only starts its branch once something is answered
+        let answered = ai_message.as_ref().is_some_and(|ai| !ai.content.is_empty() || !ai.tool_calls.is_empty());
+        let pair = user_message.zip(ai_message)
+            .filter(|_| retry_of.is_some() || truncated.is_empty() || answered);
+        let Some((user_message, ai_message)) = pair else {
+            // nothing came back, keep the turns that were there
+            if let Some(frame) = truncated.first().cloned() {
+                thread.messages.extend(truncated);
+                self.update_flag.store(true, Ordering::Release);
+                return Ok(frame);
+            }
             return Ok(Arc::new(MessageFrame::default()));
         };
 
+        let alternates = match retry_of {
+            Some(previous) => {
+                let mut previous = MessageFrame::clone(&previous);
+                let mut alternates = std::mem::take(&mut previous.alternates);
+                alternates.push(Arc::new(previous));
+                alternates
+            }
+            None => vec![],
+        };
+
         let frame = Arc::new(MessageFrame {
             ai: ai_message,
             user: user_message,
+            tools,
+            interrupted,
+            error,
+            alternates,
         });
 
         info!("flushed frame: user: {:?}, ai: {:?}", frame.user.id, frame.ai.id);
@@ -112,23 +354,30 @@ impl ChatWriter {
 
 #[derive(Debug)]
 pub struct ChatReader {
-    thread: Arc<Mutex<ChatThreadInner>>,
-    thread_id: Option<String>,
+    registry: ChatRegistry,
+    key: Option<ThreadKey>,
     messages: Vec<Arc<MessageFrame>>,
     update_flag: Arc<AtomicBool>,
 }
 
 impl ChatReader {
-    pub async fn read(&mut self) -> &[Arc<MessageFrame>] {
+    pub async fn read(&mut self, key: ThreadKey) -> &[Arc<MessageFrame>] {
         let updated = self.update_flag.load(Ordering::Relaxed);
 
-        if updated {
+        if updated || self.key != Some(key) {
             self.update_flag.store(false, Ordering::Release);
-            let thread = self.thread.lock().await;
+            self.key = Some(key);
             self.messages.clear();
-            self.messages.extend(thread.messages.iter().cloned());
+            if let Some(thread) = self.registry.get(key).await {
+                self.messages.extend(thread.lock().await.messages.iter().cloned());
+            }
         }
 
         &self.messages
     }
+
+    #[inline]
+    pub fn registry(&self) -> &ChatRegistry {
+        &self.registry
+    }
 }
@@ -0,0 +1,125 @@
+use std::path::PathBuf;
+
+use clap::{Args, Parser, Subcommand};
+use reqwest::Url;
+
+use crate::host::{HostConfig, HostTarget, DEFAULT_HOST_COMMAND, DEFAULT_HOST_REPO, DEFAULT_HOST_TAG};
+
+#[derive(Debug, Parser)]
+#[command(version, about = "A terminal client for the Dive MCP host")]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    /// Directory holding the tive and host configuration
+    #[arg(long, global = true, env = "TIVE_CONFIG_DIR")]
+    pub config_dir: Option<PathBuf>,
+
+    /// Log level or filter directive, e.g. `debug` or `tive=trace`
+    #[arg(long, global = true, env = "TIVE_LOGLEVEL")]
+    pub log_level: Option<String>,
+
+    #[command(flatten)]
+    pub host: HostArgs,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Open the full-screen chat interface (default)
+    Tui,
+    /// Send a single prompt and stream the answer to stdout
+    Ask(AskArgs),
+    /// List the chats stored by the host
+    Chats(ChatsArgs),
+    /// Show the effective configuration
+    Config,
+    /// Inspect the host installation
+    #[command(subcommand)]
+    Host(HostCommand),
+}
+
+#[derive(Debug, Args)]
+pub struct AskArgs {
+    /// The prompt, read from stdin when omitted
+    pub prompt: Option<String>,
+
+    /// Continue an existing chat instead of starting a new one
+    #[arg(long)]
+    pub chat_id: Option<String>,
+
+    /// Print every response event as a JSON line, including tool calls and results
+    #[arg(long)]
+    pub json: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct ChatsArgs {
+    /// Print the chats as a JSON array
+    #[arg(long)]
+    pub json: bool,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum HostCommand {
+    /// Show where the host is installed and which version it runs
+    Status,
+    /// Install or update the host without starting it
+    Install,
+}
+
+#[derive(Debug, Args)]
+pub struct HostArgs {
+    /// Git repository the host is cloned from
+    #[arg(long, global = true, env = "TIVE_HOST_REPO", default_value = DEFAULT_HOST_REPO)]
+    pub host_repo: String,
+
+    /// Host tag or commit to run
+    #[arg(long, global = true, env = "TIVE_HOST_TAG", default_value = DEFAULT_HOST_TAG)]
+    pub host_tag: String,
+
+    /// Install the host from this directory or tarball instead of cloning it
+    #[arg(long, global = true, env = "TIVE_HOST_SOURCE")]
+    pub host_source: Option<PathBuf>,
+
+    /// Port the host listens on, 0 picks a free one
+    #[arg(long, global = true, env = "TIVE_HOST_PORT", default_value_t = 0)]
+    pub host_port: u16,
+
+    /// Command starting the host http server
+    #[arg(long, global = true, env = "TIVE_HOST_COMMAND", default_value = DEFAULT_HOST_COMMAND)]
+    pub host_command: String,
+
+    /// Attach to a running host at this http(s) url instead of spawning one
+    #[arg(long, global = true, env = "TIVE_HOST_URL", value_parser = parse_host_url)]
+    pub host_url: Option<Url>,
+
+    /// Bearer token sent to an attached host
+    #[arg(long, global = true, env = "TIVE_HOST_TOKEN", hide_env_values = true, requires = "host_url")]
+    pub host_token: Option<String>,
+}
+
+impl HostArgs {
+    pub fn target(&self) -> HostTarget {
+        match &self.host_url {
+            Some(url) => HostTarget::Attach {
+                url: url.clone(),
+                token: self.host_token.clone(),
+            },
+            None => HostTarget::Spawn(HostConfig {
+                repo: self.host_repo.clone(),
+                tag: self.host_tag.clone(),
+                source: self.host_source.clone(),
+                port: self.host_port,
+                command: self.host_command.split_whitespace().map(String::from).collect(),
+            }),
+        }
+    }
+}
+
+fn parse_host_url(s: &str) -> Result<Url, String> {
+    let url = Url::parse(s).map_err(|e| e.to_string())?;
+    match url.scheme() {
+        "http" | "https" => Ok(url),
+        scheme => Err(format!("unsupported scheme `{}`, expected http or https", scheme)),
+    }
+}
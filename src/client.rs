@@ -1,24 +1,130 @@
 use std::{
     collections::HashMap,
+    net::{IpAddr, SocketAddr},
     pin::Pin,
+    sync::{Arc, RwLock},
     time::Duration,
 };
 
 use anyhow::Result;
 use futures::Stream;
 use futures_util::StreamExt;
-use reqwest::Client;
-use serde::Deserialize;
+use reqwest::{
+    header::{HeaderMap, HeaderValue, AUTHORIZATION},
+    Client, Response, Url,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
 use tokio::time::timeout;
 
-#[derive(Debug, Deserialize)]
+use crate::{
+    mcp_config::{McpConfig, McpServerStatus},
+    message::Message,
+    model_config::{ModelConfig, ProviderConfig},
+    sse::SseDecoder,
+};
+
+/// How long a host may take to answer its first ping.
+const WAIT_FOR_SERVER: Duration = Duration::from_secs(5);
+/// How long a model provider may take to list its models.
+const LIST_MODELS_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct ChatInfo {
     pub id: String,
-    #[serde(rename = "title")]
-    pub _title: String,
+    pub title: String,
+    #[serde(rename = "createdAt", default, skip_serializing_if = "Option::is_none")]
+    pub created_at: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ChatDetail {
+    pub chat: ChatInfo,
+    pub messages: Vec<Message>,
+}
+
+#[derive(Debug, Deserialize)]
+struct HostResponse<T> {
+    success: bool,
+    message: Option<String>,
+    data: Option<T>,
+}
+
+impl<T> HostResponse<T> {
+    fn into_ok(self) -> Result<()> {
+        if self.success {
+            Ok(())
+        } else {
+            Err(anyhow::anyhow!(self.message.unwrap_or_else(|| "host request failed".to_string())))
+        }
+    }
+
+    fn into_result(self) -> Result<T> {
+        match (self.success, self.data) {
+            (true, Some(data)) => Ok(data),
+            _ => Err(anyhow::anyhow!(self.message.unwrap_or_else(|| "host returned no data".to_string()))),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct ModelConfigResponse {
+    success: bool,
+    message: Option<String>,
+    config: Option<ModelConfig>,
+}
+
+#[derive(Debug, Deserialize)]
+struct McpConfigResponse {
+    success: bool,
+    message: Option<String>,
+    config: Option<McpConfig>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ToolsResponse {
+    success: bool,
+    message: Option<String>,
+    #[serde(default)]
+    tools: Vec<McpServerStatus>,
+}
+
+/// The model list of a provider: openai-like apis answer `data`, ollama and
+/// google answer `models`.
+#[derive(Debug, Default, Deserialize)]
+struct ModelList {
+    #[serde(default)]
+    data: Vec<ModelEntry>,
+    #[serde(default)]
+    models: Vec<ModelEntry>,
 }
 
 #[derive(Debug, Deserialize)]
+struct ModelEntry {
+    #[serde(alias = "name")]
+    id: String,
+    /// Set by google only, which lists embedding models too.
+    #[serde(rename = "supportedGenerationMethods")]
+    methods: Option<Vec<String>>,
+}
+
+impl ModelList {
+    /// The names of the chat models, sorted.
+    fn names(self) -> Vec<String> {
+        let mut names: Vec<_> = self.data.into_iter()
+            .chain(self.models)
+            .filter(|entry| {
+                entry.methods.as_ref().is_none_or(|methods| methods.iter().any(|method| method == "generateContent"))
+            })
+            .map(|entry| entry.id.strip_prefix("models/").map(str::to_string).unwrap_or(entry.id))
+            .collect();
+        names.sort();
+        names.dedup();
+        names
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize)]
 pub struct MessageInfo {
     #[serde(rename = "userMessageId")]
     pub user_message_id: String,
@@ -26,19 +132,43 @@ pub struct MessageInfo {
     pub assistant_message_id: String,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 pub struct ToolCall {
     pub name: String,
-    pub arguments: String,
+    pub arguments: Value,
 }
 
-#[derive(Debug, Deserialize)]
+impl ToolCall {
+    pub fn pretty_arguments(&self) -> String {
+        pretty_json(&self.arguments)
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize)]
 pub struct ToolResult {
     pub name: String,
-    pub result: String,
+    pub result: Value,
 }
 
-#[derive(Debug, Deserialize)]
+impl ToolResult {
+    pub fn pretty_result(&self) -> String {
+        pretty_json(&self.result)
+    }
+}
+
+/// The host may send JSON either as a value or encoded in a string.
+fn pretty_json(value: &Value) -> String {
+    match value {
+        Value::String(s) => serde_json::from_str::<Value>(s)
+            .ok()
+            .filter(|v| v.is_object() || v.is_array())
+            .and_then(|v| serde_json::to_string_pretty(&v).ok())
+            .unwrap_or_else(|| s.clone()),
+        v => serde_json::to_string_pretty(v).unwrap_or_else(|_| v.to_string()),
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize)]
 #[serde(tag = "type", content = "content")]
 pub enum ChatResponse {
     #[serde(rename = "text")]
@@ -51,6 +181,21 @@ pub enum ChatResponse {
     ChatInfo(ChatInfo),
     #[serde(rename = "message_info")]
     MessageInfo(MessageInfo),
+    #[serde(rename = "error")]
+    Error(Value),
+}
+
+/// The host reports errors either as a plain string or as an object holding
+/// a `message`.
+pub fn error_message(error: &Value) -> String {
+    match error {
+        Value::String(s) => s.clone(),
+        Value::Object(map) => match map.get("message") {
+            Some(Value::String(message)) => message.clone(),
+            _ => error.to_string(),
+        },
+        _ => error.to_string(),
+    }
 }
 
 #[derive(Debug, Deserialize)]
@@ -58,24 +203,50 @@ struct MessageStreamFrame {
     message: String,
 }
 
+#[derive(Clone)]
 pub struct ChatClient {
     client: Client,
-    ip: String,
-    port: u16,
+    /// Talks to model providers, without the host's token.
+    providers: Client,
+    base: Arc<RwLock<Url>>,
 }
 
 impl ChatClient {
-    pub fn new(ip: String, port: u16) -> Self {
-        Self {
-            client: Client::new(),
-            ip,
-            port,
+    /// Connect to `base`, sending `token` as a bearer token with every request.
+    pub fn new(base: Url, token: Option<&str>) -> Result<Self> {
+        let mut headers = HeaderMap::new();
+        if let Some(token) = token {
+            let mut value = HeaderValue::from_str(&format!("Bearer {}", token))?;
+            value.set_sensitive(true);
+            headers.insert(AUTHORIZATION, value);
         }
+
+        Ok(Self {
+            client: Client::builder().default_headers(headers).build()?,
+            providers: Client::builder().timeout(LIST_MODELS_TIMEOUT).build()?,
+            base: Arc::new(RwLock::new(with_trailing_slash(base))),
+        })
+    }
+
+    /// Connect to a host spawned on this machine.
+    pub fn local(ip: &str, port: u16) -> Result<Self> {
+        Self::new(local_url(ip, port)?, None)
+    }
+
+    /// Point this client, and every clone of it, at a respawned local host.
+    pub fn set_local(&self, ip: &str, port: u16) -> Result<()> {
+        *self.base.write().unwrap() = local_url(ip, port)?;
+        Ok(())
+    }
+
+    /// The address of the host, for display.
+    pub fn endpoint(&self) -> String {
+        self.base.read().unwrap().as_str().trim_end_matches('/').to_string()
     }
 
     #[inline]
     fn url(&self, path: &str) -> String {
-        format!("http://{}:{}/{}", self.ip, self.port, path)
+        format!("{}{}", self.base.read().unwrap(), path)
     }
 
     pub fn chat_stream(&self, message: &str, id: Option<&str>) -> ChatResponseStream {
@@ -84,30 +255,47 @@ impl ChatClient {
             params.insert("chatId", id);
         }
 
-        let url = self.url("api/chat");
+        self.stream("api/chat", &params)
+    }
+
+    /// Generate the answer with the ai message `message_id` again, the host
+    /// drops every message after it.
+    pub fn retry_stream(&self, chat_id: &str, message_id: &str) -> ChatResponseStream {
+        let params = HashMap::from([("chatId", chat_id), ("messageId", message_id)]);
+        self.stream("api/chat/retry", &params)
+    }
+
+    /// Replace the user message `message_id` by `content` and answer it, the
+    /// host drops every message after it.
+    pub fn edit_stream(&self, chat_id: &str, message_id: &str, content: &str) -> ChatResponseStream {
+        let params = HashMap::from([("chatId", chat_id), ("messageId", message_id), ("content", content)]);
+        self.stream("api/chat/edit", &params)
+    }
 
-        let request = self.client.post(url).form(&params);
+    fn stream(&self, path: &str, params: &HashMap<&str, &str>) -> ChatResponseStream {
+        let request = self.client.post(self.url(path)).form(params);
 
         let stream = async_stream::try_stream! {
-            let response = request.send().await?;
+            let response = check_status(request.send().await?).await?;
+
             let mut bytes_stream = response.bytes_stream();
+            let mut decoder = SseDecoder::default();
 
-            while let Some(item) = bytes_stream.next().await {
-                let item = item?;
+            'stream: while let Some(item) = bytes_stream.next().await {
+                for event in decoder.push(&item?) {
+                    // only plain messages carry chat responses
+                    if event.event.as_deref().is_some_and(|e| e != "message") {
+                        continue;
+                    }
+                    if event.data.trim().eq_ignore_ascii_case("[DONE]") {
+                        break 'stream;
+                    }
 
-                if item.is_empty() || item.len() < 6 {
-                    break;
-                }
+                    let frame = serde_json::from_str::<MessageStreamFrame>(&event.data)?;
+                    let response = serde_json::from_str::<ChatResponse>(&frame.message)?;
 
-                let body = item.slice(6..);
-                if body.eq_ignore_ascii_case(b"[DONE]\n\n") {
-                    break;
+                    yield response;
                 }
-
-                let frame = serde_json::from_slice::<MessageStreamFrame>(&body)?;
-                let response = serde_json::from_str::<ChatResponse>(&frame.message)?;
-
-                yield response;
             }
         };
 
@@ -116,23 +304,191 @@ impl ChatClient {
         }
     }
 
+    pub async fn list_chats(&self) -> Result<Vec<ChatInfo>> {
+        self.client.get(self.url("api/chat/list"))
+            .send()
+            .await?
+            .json::<HostResponse<Vec<ChatInfo>>>()
+            .await?
+            .into_result()
+    }
+
+    pub async fn get_chat(&self, id: &str) -> Result<ChatDetail> {
+        self.client.get(self.url(&format!("api/chat/{}", id)))
+            .send()
+            .await?
+            .json::<HostResponse<ChatDetail>>()
+            .await?
+            .into_result()
+    }
+
+    /// The model config the host runs with.
+    pub async fn model_config(&self) -> Result<ModelConfig> {
+        let response = self.client.get(self.url("api/config/model"))
+            .send()
+            .await?
+            .json::<ModelConfigResponse>()
+            .await?;
+
+        match (response.success, response.config) {
+            (true, Some(config)) => Ok(config),
+            _ => Err(anyhow::anyhow!(response.message.unwrap_or_else(|| "host returned no model config".to_string()))),
+        }
+    }
+
+    /// Replace the whole model config, the host saves it and switches to it
+    /// without restarting.
+    pub async fn replace_model_config(&self, config: &ModelConfig) -> Result<()> {
+        self.client.post(self.url("api/config/model/replaceAll"))
+            .json(config)
+            .send()
+            .await?
+            .json::<HostResponse<Value>>()
+            .await?
+            .into_ok()
+    }
+
+    /// The mcp servers the host is configured with.
+    pub async fn mcp_config(&self) -> Result<McpConfig> {
+        let response = self.client.get(self.url("api/config/mcpserver"))
+            .send()
+            .await?
+            .json::<McpConfigResponse>()
+            .await?;
+
+        match (response.success, response.config) {
+            (true, Some(config)) => Ok(config),
+            _ => Err(anyhow::anyhow!(response.message.unwrap_or_else(|| "host returned no mcp config".to_string()))),
+        }
+    }
+
+    /// Replace the whole mcp config, the host saves it and restarts the
+    /// servers that changed.
+    pub async fn replace_mcp_config(&self, config: &McpConfig) -> Result<()> {
+        self.client.post(self.url("api/config/mcpserver"))
+            .json(config)
+            .send()
+            .await?
+            .json::<HostResponse<Value>>()
+            .await?
+            .into_ok()
+    }
+
+    /// Every mcp server the host runs, with its tools.
+    pub async fn list_tools(&self) -> Result<Vec<McpServerStatus>> {
+        let response = self.client.get(self.url("api/tools"))
+            .send()
+            .await?
+            .json::<ToolsResponse>()
+            .await?;
+
+        if response.success {
+            Ok(response.tools)
+        } else {
+            Err(anyhow::anyhow!(response.message.unwrap_or_else(|| "host failed to list tools".to_string())))
+        }
+    }
+
+    /// The models `provider` offers. The host only knows the configured one,
+    /// so the provider itself is asked, with the configured key and base url.
+    pub async fn provider_models(&self, provider: &ProviderConfig) -> Result<Vec<String>> {
+        let key = provider.api_key.as_deref().filter(|key| !key.is_empty());
+        let base = |default: &str| provider.base_url()
+            .filter(|url| !url.is_empty())
+            .unwrap_or(default)
+            .trim_end_matches('/')
+            .to_string();
+
+        let request = match provider.model_provider.as_str() {
+            kind @ ("openai" | "openai_compatible" | "deepseek" | "mistralai") => {
+                let default = match kind {
+                    "deepseek" => "https://api.deepseek.com",
+                    "mistralai" => "https://api.mistral.ai/v1",
+                    _ => "https://api.openai.com/v1",
+                };
+                let request = self.providers.get(format!("{}/models", base(default)));
+                match key {
+                    Some(key) => request.bearer_auth(key),
+                    None => request,
+                }
+            }
+            "ollama" => self.providers.get(format!("{}/api/tags", base("http://localhost:11434"))),
+            "anthropic" => self.providers.get(format!("{}/v1/models", base("https://api.anthropic.com")))
+                .query(&[("limit", "1000")])
+                .header("x-api-key", key.unwrap_or_default())
+                .header("anthropic-version", "2023-06-01"),
+            "google-genai" => self.providers.get("https://generativelanguage.googleapis.com/v1beta/models")
+                .query(&[("key", key.unwrap_or_default()), ("pageSize", "1000")]),
+            other => return Err(anyhow::anyhow!("cannot list the models of {}", other)),
+        };
+
+        let response = request.send().await?;
+        let status = response.status();
+        if !status.is_success() {
+            return Err(anyhow::anyhow!("{} answered {}", provider.model_provider, status));
+        }
+        Ok(response.json::<ModelList>().await?.names())
+    }
+
+    pub async fn abort(&self, id: &str) -> Result<()> {
+        self.client.post(self.url(&format!("api/chat/{}/abort", id)))
+            .send()
+            .await?
+            .json::<HostResponse<Value>>()
+            .await?
+            .into_ok()
+    }
+
     pub async fn wait_for_server(&self) -> Result<()> {
         let client = self.client.clone();
         let url = self.url("ping");
         let handle = tokio::spawn(async move {
             loop {
-                let res = client.get(&url).send().await;
-                if res.is_ok() {
-                    break;
+                if let Ok(res) = client.get(&url).send().await {
+                    break res.status();
                 }
 
                 tokio::time::sleep(std::time::Duration::from_millis(50)).await;
             }
         });
 
-        timeout(Duration::from_secs(1), handle).await
-            .map_or(Err(anyhow::anyhow!("timeout")), |_| Ok(()))
+        let status = timeout(WAIT_FOR_SERVER, handle).await
+            .map_err(|_| anyhow::anyhow!("timeout waiting for host at {}", self.endpoint()))??;
+        if !status.is_success() {
+            return Err(anyhow::anyhow!("host at {} answered {}", self.endpoint(), status));
+        }
+
+        Ok(())
+    }
+}
+
+/// Turn a non-2xx response into an error carrying the host's message.
+async fn check_status(response: Response) -> Result<Response> {
+    let status = response.status();
+    if status.is_success() {
+        return Ok(response);
     }
+
+    let body = response.text().await.unwrap_or_default();
+    let message = serde_json::from_str::<HostResponse<Value>>(&body).ok()
+        .and_then(|response| response.message)
+        .unwrap_or(body);
+    Err(anyhow::anyhow!("host answered {}: {}", status, message.trim()))
+}
+
+/// IPv6 addresses need brackets in a URL, which `SocketAddr` adds.
+fn local_url(ip: &str, port: u16) -> Result<Url> {
+    let ip: IpAddr = ip.parse()
+        .map_err(|_| anyhow::anyhow!("host reported an invalid address: {}", ip))?;
+    Ok(Url::parse(&format!("http://{}/", SocketAddr::new(ip, port)))?)
+}
+
+/// Paths are appended to the base, so it has to end in a slash.
+fn with_trailing_slash(mut url: Url) -> Url {
+    if !url.path().ends_with('/') {
+        url.set_path(&format!("{}/", url.path()));
+    }
+    url
 }
 
 pub struct ChatResponseStream {
@@ -146,3 +502,33 @@ impl Stream for ChatResponseStream {
         self.stream.as_mut().poll_next(cx)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn model_list_names() {
+        let openai = r#"{"object": "list", "data": [{"id": "gpt-4o", "object": "model"}, {"id": "gpt-4.1"}]}"#;
+        let names = serde_json::from_str::<ModelList>(openai).unwrap().names();
+        assert_eq!(names, vec!["gpt-4.1", "gpt-4o"]);
+
+        let ollama = r#"{"models": [{"name": "qwen3:8b", "model": "qwen3:8b", "size": 1}]}"#;
+        let names = serde_json::from_str::<ModelList>(ollama).unwrap().names();
+        assert_eq!(names, vec!["qwen3:8b"]);
+
+        let google = r#"{"models": [
+            {"name": "models/gemini-2.5-pro", "supportedGenerationMethods": ["generateContent", "countTokens"]},
+            {"name": "models/text-embedding-004", "supportedGenerationMethods": ["embedContent"]}
+        ]}"#;
+        let names = serde_json::from_str::<ModelList>(google).unwrap().names();
+        assert_eq!(names, vec!["gemini-2.5-pro"]);
+    }
+
+    #[test]
+    fn local_url_brackets_ipv6() {
+        assert_eq!(local_url("127.0.0.1", 61990).unwrap().as_str(), "http://127.0.0.1:61990/");
+        assert_eq!(local_url("::1", 61990).unwrap().as_str(), "http://[::1]:61990/");
+        assert!(local_url("not an ip", 61990).is_err());
+    }
+}
@@ -0,0 +1,14 @@
+use std::io::{stdout, Write};
+
+use anyhow::Result;
+use base64::{engine::general_purpose::STANDARD, Engine};
+
+/// Copy `text` to the system clipboard with an OSC 52 escape sequence.
+///
+/// The terminal does the copy, so this also works over ssh.
+pub fn copy(text: &str) -> Result<()> {
+    let mut out = stdout().lock();
+    write!(out, "\x1b]52;c;{}\x07", STANDARD.encode(text))?;
+    out.flush()?;
+    Ok(())
+}
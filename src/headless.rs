@@ -0,0 +1,257 @@
+use std::{
+    io::{IsTerminal, Read},
+    process::ExitCode,
+};
+
+use anyhow::Result;
+use futures::StreamExt;
+use tokio::{
+    io::{AsyncWriteExt, Stdout},
+    signal,
+};
+use tracing::error;
+
+use crate::{
+    cli::{AskArgs, ChatsArgs},
+    client::{self, ChatClient, ChatResponse},
+    host::{self, HostConnection, HostTarget},
+    logger,
+    provision,
+    shared::PROJECT_DIRS,
+};
+
+/// The request was bad, e.g. an empty prompt.
+const EXIT_USAGE: u8 = 2;
+/// Interrupted by ctrl-c, following the shell convention of 128 + SIGINT.
+const EXIT_INTERRUPTED: u8 = 130;
+
+/// Send one prompt to a fresh host and stream the answer to stdout.
+pub async fn ask(target: HostTarget, args: AskArgs) -> Result<ExitCode> {
+    let prompt = match args.prompt {
+        Some(prompt) => prompt,
+        None if std::io::stdin().is_terminal() => {
+            eprintln!("tive: no prompt given, pass it as an argument or on stdin");
+            return Ok(ExitCode::from(EXIT_USAGE));
+        }
+        None => {
+            let mut prompt = String::new();
+            std::io::stdin().read_to_string(&mut prompt)?;
+            prompt
+        }
+    };
+
+    if prompt.trim().is_empty() {
+        eprintln!("tive: the prompt is empty");
+        return Ok(ExitCode::from(EXIT_USAGE));
+    }
+
+    let Some(HostConnection { process: _process, client, .. }) = host::connect(target).await? else {
+        return Ok(ExitCode::from(EXIT_INTERRUPTED));
+    };
+
+    let mut output = Output {
+        stdout: tokio::io::stdout(),
+        json: args.json,
+        ends_with_newline: true,
+    };
+    let mut chat_id = args.chat_id.clone();
+    let mut failed = None;
+    let mut stream = client.chat_stream(&prompt, args.chat_id.as_deref());
+
+    loop {
+        let response = tokio::select! {
+            _ = signal::ctrl_c() => {
+                if let Some(chat_id) = &chat_id
+                    && let Err(e) = client.abort(chat_id).await
+                {
+                    error!("Failed to abort chat {}: {}", chat_id, e);
+                }
+                output.finish().await?;
+                return Ok(ExitCode::from(EXIT_INTERRUPTED));
+            }
+            response = stream.next() => response,
+        };
+
+        match response {
+            None => break,
+            Some(Ok(response)) => {
+                match &response {
+                    ChatResponse::ChatInfo(info) => {
+                        chat_id.get_or_insert_with(|| info.id.clone());
+                    }
+                    ChatResponse::Error(error) => failed = Some(client::error_message(error)),
+                    _ => {}
+                }
+                output.write(&response).await?;
+            }
+            Some(Err(e)) => {
+                output.finish().await?;
+                eprintln!("tive: {}", e);
+                return Ok(ExitCode::FAILURE);
+            }
+        }
+    }
+
+    output.finish().await?;
+    if let Some(error) = failed {
+        eprintln!("tive: {}", error);
+        return Ok(ExitCode::FAILURE);
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Print the chats stored by the host, one per line.
+pub async fn chats(target: HostTarget, args: ChatsArgs) -> Result<ExitCode> {
+    let Some(HostConnection { process: _process, client, .. }) = host::connect(target).await? else {
+        return Ok(ExitCode::from(EXIT_INTERRUPTED));
+    };
+
+    let chats = match client.list_chats().await {
+        Ok(chats) => chats,
+        Err(e) => {
+            eprintln!("tive: {}", e);
+            return Ok(ExitCode::FAILURE);
+        }
+    };
+
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&chats)?);
+    } else {
+        for chat in chats {
+            println!("{}\t{}\t{}", chat.id, chat.created_at.as_deref().unwrap_or("-"), chat.title);
+        }
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Print the directories and host settings in effect.
+pub fn config(target: &HostTarget) -> ExitCode {
+    let dirs = &*PROJECT_DIRS;
+    println!("config dir:   {}", dirs.config.display());
+    println!("data dir:     {}", dirs.data.display());
+    println!("cache dir:    {}", dirs.cache.display());
+    println!("log file:     {}", logger::log_file().display());
+    println!("host log:     {}", dirs.host_log_file().display());
+    match target {
+        HostTarget::Spawn(config) => {
+            println!("host repo:    {}", config.repo);
+            println!("host tag:     {}", config.tag);
+            if let Some(source) = &config.source {
+                println!("host source:  {}", source.display());
+            }
+            println!("host port:    {}", config.port);
+            println!("host command: {}", config.command.join(" "));
+        }
+        HostTarget::Attach { url, token } => {
+            println!("host url:     {}", url);
+            println!("host token:   {}", if token.is_some() { "set" } else { "none" });
+        }
+    }
+    ExitCode::SUCCESS
+}
+
+/// Report the host installation without starting it, or whether an attached
+/// host answers.
+pub async fn host_status(target: HostTarget) -> Result<ExitCode> {
+    let config = match target {
+        HostTarget::Spawn(config) => config,
+        HostTarget::Attach { url, token } => {
+            let client = ChatClient::new(url, token.as_deref())?;
+            println!("url:       {}", client.endpoint());
+            return Ok(match client.wait_for_server().await {
+                Ok(()) => {
+                    println!("reachable: yes");
+                    ExitCode::SUCCESS
+                }
+                Err(e) => {
+                    println!("reachable: no ({})", e);
+                    ExitCode::FAILURE
+                }
+            });
+        }
+    };
+
+    let path = PROJECT_DIRS.host_data_dir();
+    let installed = provision::installed(&config, &path).await;
+    let runner = config.command.first().map(String::as_str).unwrap_or_default();
+    let runner_found = std::env::var_os("PATH")
+        .map(|paths| std::env::split_paths(&paths).any(|dir| dir.join(runner).is_file()))
+        .unwrap_or(false);
+
+    println!("path:      {}", path.display());
+    match &config.source {
+        Some(source) => println!("source:    {}", source.display()),
+        None => println!("source:    {} {}", config.repo, config.tag),
+    }
+    println!("installed: {}{}",
+        installed.version.as_deref().unwrap_or("no"),
+        if installed.version.is_some() && !installed.current { " (outdated)" } else { "" },
+    );
+    println!("command:   {} ({})", config.command.join(" "), if runner_found { "found" } else { "not found" });
+
+    if installed.current && runner_found {
+        Ok(ExitCode::SUCCESS)
+    } else {
+        Ok(ExitCode::FAILURE)
+    }
+}
+
+/// Provision the host ahead of time, e.g. while a network is at hand.
+pub async fn host_install(target: HostTarget) -> Result<ExitCode> {
+    let HostTarget::Spawn(config) = target else {
+        eprintln!("tive: an attached host is not installed by tive");
+        return Ok(ExitCode::from(EXIT_USAGE));
+    };
+
+    let path = PROJECT_DIRS.host_data_dir();
+    match provision::provision(&config, &path).await {
+        Ok(()) => {
+            let installed = provision::installed(&config, &path).await;
+            println!("installed {} in {}", installed.version.as_deref().unwrap_or("host"), path.display());
+            Ok(ExitCode::SUCCESS)
+        }
+        Err(e) => {
+            eprintln!("tive: {}", e);
+            Ok(ExitCode::FAILURE)
+        }
+    }
+}
+
+struct Output {
+    stdout: Stdout,
+    json: bool,
+    ends_with_newline: bool,
+}
+
+impl Output {
+    /// Print plain text only, or every response as one JSON line.
+    async fn write(&mut self, response: &ChatResponse) -> Result<()> {
+        if self.json {
+            let mut line = serde_json::to_vec(response)?;
+            line.push(b'\n');
+            self.stdout.write_all(&line).await?;
+        } else if let ChatResponse::Text(text) = response {
+            if text.is_empty() {
+                return Ok(());
+            }
+            self.stdout.write_all(text.as_bytes()).await?;
+            self.ends_with_newline = text.ends_with('\n');
+        }
+
+        self.stdout.flush().await?;
+        Ok(())
+    }
+
+    /// Terminate the answer with a newline so the shell prompt starts clean.
+    async fn finish(&mut self) -> Result<()> {
+        if !self.ends_with_newline {
+            self.stdout.write_all(b"\n").await?;
+            self.ends_with_newline = true;
+        }
+
+        self.stdout.flush().await?;
+        Ok(())
+    }
+}
@@ -17,20 +17,104 @@ use tokio::{
     sync::mpsc::{channel, Receiver, Sender},
     task::JoinHandle,
 };
+use reqwest::Url;
 use serde::Deserialize;
+use tracing::{info, warn};
 
-use crate::shared::PROJECT_DIRS;
+use crate::{client::ChatClient, host_log, model_config::ModelConfig, provision, shared::PROJECT_DIRS};
 
 pub const COMMAND_ALIAS_FILE: &str = "command_alias.json";
 pub const CUSTOM_RULES_FILE: &str = "customrules";
 pub const MCP_CONFIG_FILE: &str = "mcp_config.json";
 pub const MODEL_CONFIG_FILE: &str = "model_config.json";
+/// The host database, kept in the host data directory across installs.
+pub const HOST_DB_FILE: &str = "dived.sqlite";
+
+pub const DEFAULT_HOST_REPO: &str = "https://github.com/OpenAgentPlatform/dive-mcp-host.git";
+pub const DEFAULT_HOST_TAG: &str = "v0.1.5";
+pub const DEFAULT_HOST_COMMAND: &str = "uv run dive_httpd";
+
+/// Which host to install and how to start it.
+#[derive(Debug, Clone)]
+pub struct HostConfig {
+    /// Git repository the host is cloned from.
+    pub repo: String,
+    /// Tag or commit checked out before starting.
+    pub tag: String,
+    /// Local directory or tarball installed instead of cloning `repo`.
+    pub source: Option<PathBuf>,
+    /// Port to listen on, 0 lets the host pick one.
+    pub port: u16,
+    /// Program and arguments starting the http server.
+    pub command: Vec<String>,
+}
+
+/// Where the host comes from: spawned by tive, or already running elsewhere.
+#[derive(Debug, Clone)]
+pub enum HostTarget {
+    Spawn(HostConfig),
+    Attach {
+        url: Url,
+        token: Option<String>,
+    },
+}
+
+/// A ready host, keeping the spawned process alive while it is held.
+pub struct HostConnection {
+    pub process: Option<HostProcess>,
+    /// Status bus of the spawned process.
+    pub events: Option<Receiver<HostEvent>>,
+    /// The bus message the spawned process announced its address with.
+    pub listen_message: Option<HostMessage>,
+    pub client: ChatClient,
+}
+
+/// Spawn or attach to the host and wait until it answers, `None` if
+/// interrupted by ctrl-c.
+pub async fn connect(target: HostTarget) -> Result<Option<HostConnection>> {
+    let connection = match target {
+        HostTarget::Spawn(config) => {
+            let (mut process, mut rx) = HostProcess::new(config).await?;
+            process.spawn().await?;
+
+            let message = tokio::select! {
+                _ = tokio::signal::ctrl_c() => return Ok(None),
+                message = wait_for_listen(&mut rx) => message?,
+            };
+            let (ip, port) = message.listen().unwrap_or_default();
+            info!("Host listen: {}:{}", ip, port);
+
+            HostConnection {
+                client: ChatClient::local(ip, port)?,
+                process: Some(process),
+                events: Some(rx),
+                listen_message: Some(message),
+            }
+        }
+        HostTarget::Attach { url, token } => {
+            info!("Attach to host: {}", url);
+            HostConnection {
+                process: None,
+                events: None,
+                listen_message: None,
+                client: ChatClient::new(url, token.as_deref())?,
+            }
+        }
+    };
+
+    tokio::select! {
+        _ = tokio::signal::ctrl_c() => return Ok(None),
+        ready = connection.client.wait_for_server() => ready?,
+    }
+
+    Ok(Some(connection))
+}
 
 #[derive(Debug, Clone, Deserialize)]
 pub struct HostStatus {
-    state: String,
-    last_error: Option<String>,
-    error_code: Option<String>,
+    pub state: String,
+    pub last_error: Option<String>,
+    pub error_code: Option<String>,
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -51,13 +135,21 @@ pub struct HostMessage {
     pub server: Option<HostServer>,
 }
 
+impl HostMessage {
+    pub fn listen(&self) -> Option<(&str, u16)> {
+        let listen = self.server.as_ref()?.listen.as_ref()?;
+        Some((listen.ip.as_deref()?, listen.port?))
+    }
+}
+
 #[derive(Debug, Clone)]
 pub enum HostEvent {
     BusMessage(HostMessage),
-    Error(&'static str),
+    Error(String),
 }
 
 pub struct HostProcess {
+    config: HostConfig,
     child_process: Option<Child>,
     file_path: PathBuf,
     tx: Sender<HostEvent>,
@@ -65,12 +157,13 @@ pub struct HostProcess {
 }
 
 impl HostProcess {
-    pub async fn new() -> Result<(Self, Receiver<HostEvent>)> {
+    pub async fn new(config: HostConfig) -> Result<(Self, Receiver<HostEvent>)> {
         let (tx, rx) = channel(1);
         let file_path = std::env::temp_dir().join("tive").join("bus");
 
         Ok((
             Self {
+                config,
                 child_process: None,
                 file_path,
                 tx,
@@ -80,7 +173,15 @@ impl HostProcess {
         ))
     }
 
+    /// Start the host, replacing an earlier process that has exited.
     pub async fn spawn(&mut self) -> Result<()> {
+        // the old watcher removes the bus file when dropped, so make sure it
+        // is gone before the new one creates it
+        if let Some(handle) = self.watcher_handle.take() {
+            handle.abort();
+            let _ = handle.await;
+        }
+
         let tx = self.tx.clone();
         let file_path = self.file_path.clone();
         self.watcher_handle = Some(tokio::spawn(async move {
@@ -97,10 +198,13 @@ impl HostProcess {
         tokio::fs::create_dir_all(&host_data_dir).await?;
         tokio::fs::create_dir_all(&host_cache_dir).await?;
 
-        Self::clone_host(&host_data_dir).await?;
+        provision::provision(&self.config, &host_data_dir).await
+            .map_err(|e| anyhow::anyhow!("failed to provision the host: {}", e))?;
         self.init_host_config(&host_config_dir, &host_data_dir).await?;
 
-        let mut cmd = Command::new("uv");
+        let (program, args) = self.config.command.split_first()
+            .ok_or_else(|| anyhow::anyhow!("empty host command"))?;
+        let mut cmd = Command::new(program);
         unsafe {
             cmd.pre_exec(|| {
                 // Set up process to be killed when parent dies (Linux only)
@@ -116,35 +220,47 @@ impl HostProcess {
         // set the process group to the current process
         cmd.process_group(0);
 
-        let process = cmd
-            .arg("run")
-            .arg("dive_httpd")
+        let mut process = cmd
+            .args(args)
             .arg("--port")
-            .arg("0")
+            .arg(self.config.port.to_string())
             .arg("--report_status_file")
             .arg(self.file_path.to_string_lossy().to_string())
             .env("PATH", env!("PATH"))
             .env("DIVE_CONFIG_DIR", host_config_dir.to_string_lossy().to_string())
             .env("RESOURCE_DIR", host_cache_dir.to_string_lossy().to_string())
-            .stderr(Stdio::null())
-            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .stdout(Stdio::piped())
             .current_dir(&host_data_dir)
             .spawn()?;
 
+        host_log::capture(&mut process, &dirs.host_log_file()).await?;
         self.child_process = Some(process);
         Ok(())
     }
 
+    pub fn pid(&self) -> Option<u32> {
+        self.child_process.as_ref()?.id()
+    }
+
+    /// Wait for the host process to exit, forever if it was never spawned.
+    pub async fn wait(&mut self) -> std::io::Result<ExitStatus> {
+        match self.child_process.as_mut() {
+            Some(child) => child.wait().await,
+            None => std::future::pending().await,
+        }
+    }
+
     async fn init_host_config(&self, config_dir: &Path, db_dir: &Path) -> Result<()> {
         create_file_if_not_exists(&config_dir.join(COMMAND_ALIAS_FILE), b"{}").await?;
         create_file_if_not_exists(&config_dir.join(CUSTOM_RULES_FILE), b"").await?;
         create_file_if_not_exists(&config_dir.join(MCP_CONFIG_FILE), b"{\"mcpServers\":{}}").await?;
-        create_file_if_not_exists(&config_dir.join(MODEL_CONFIG_FILE), b"{\"activeProvider\":\"fake\",\"enableTools\":true,\"disableDiveSystemPrompt\":false}").await?;
+        create_file_if_not_exists(&config_dir.join(MODEL_CONFIG_FILE), &serde_json::to_vec(&ModelConfig::default())?).await?;
 
         let db_path = db_dir.to_string_lossy().to_string();
         create_file_if_not_exists(&config_dir.join("dive_httpd.json"), format!("{{
     \"db\": {{
-        \"uri\": \"sqlite:///{}/dived.sqlite\",
+        \"uri\": \"sqlite:///{}/{}\",
         \"pool_size\": 5,
         \"pool_recycle\": 60,
         \"max_overflow\": 10,
@@ -153,40 +269,16 @@ impl HostProcess {
         \"migrate\": true
         }},
     \"checkpointer\": {{
-        \"uri\": \"sqlite:///{}/dived.sqlite\"
+        \"uri\": \"sqlite:///{}/{}\"
     }}
 }}",
             &db_path,
+            HOST_DB_FILE,
             &db_path,
+            HOST_DB_FILE,
         ).trim().as_bytes()).await?;
         Ok(())
     }
-
-    async fn clone_host(path: &Path) -> std::io::Result<ExitStatus> {
-        if !std::fs::exists(path)? {
-            Command::new("git")
-            .arg("clone")
-            .arg("https://github.com/OpenAgentPlatform/dive-mcp-host.git")
-            .arg(path)
-            .stderr(Stdio::null())
-            .stdout(Stdio::null())
-            .env("PATH", std::env::var("PATH").unwrap_or_default())
-            .spawn()?
-            .wait()
-            .await?;
-        }
-
-        Command::new("git")
-            .arg("switch")
-            .arg("v0.1.5")
-            .current_dir(path)
-            .stderr(Stdio::null())
-            .stdout(Stdio::null())
-            .env("PATH", std::env::var("PATH").unwrap_or_default())
-            .spawn()?
-            .wait()
-            .await
-    }
 }
 
 impl Drop for HostProcess {
@@ -258,7 +350,7 @@ impl FileWatcher {
                         }
                     }
                     Err(_) => {
-                        let _ = self.tx.send(HostEvent::Error("Failed to read host message")).await;
+                        let _ = self.tx.send(HostEvent::Error("Failed to read host message".to_string())).await;
                     }
                     _ => {}
                 }
@@ -269,6 +361,22 @@ impl FileWatcher {
     }
 }
 
+/// Wait for the host to report the address it listens on, returning that
+/// bus message.
+pub async fn wait_for_listen(rx: &mut Receiver<HostEvent>) -> Result<HostMessage> {
+    while let Some(evt) = rx.recv().await {
+        match evt {
+            HostEvent::BusMessage(msg) if msg.listen().is_some() => return Ok(msg),
+            HostEvent::BusMessage(msg) => {
+                warn!("Host message without listen address: {:?}", msg);
+            }
+            HostEvent::Error(e) => return Err(anyhow::anyhow!(e)),
+        }
+    }
+
+    Err(anyhow::anyhow!("host exited before reporting its listen address"))
+}
+
 async fn create_file_if_not_exists(path: &Path, content: &[u8]) -> Result<()> {
     if !tokio::fs::try_exists(path).await? {
         let file = File::create(path).await?;
@@ -0,0 +1,112 @@
+use std::{path::{Path, PathBuf}, sync::Arc};
+
+use anyhow::Result;
+use tokio::{
+    fs::{File, OpenOptions},
+    io::{AsyncBufReadExt, AsyncRead, AsyncWriteExt, BufReader},
+    process::Child,
+    sync::Mutex,
+};
+use tracing::error;
+
+/// Size after which the host log is rotated.
+const MAX_SIZE: u64 = 5 * 1024 * 1024;
+/// Rotated host logs kept next to the current one, as `<name>.1` and so on.
+const KEEP: usize = 3;
+
+/// Copy the stdout and stderr of `child` line by line into the rotating log at
+/// `path`, until the process closes them.
+pub async fn capture(child: &mut Child, path: &Path) -> Result<()> {
+    let file = Arc::new(Mutex::new(RotatingFile::open(path.to_path_buf()).await?));
+
+    if let Some(stdout) = child.stdout.take() {
+        tokio::spawn(pump(stdout, file.clone()));
+    }
+    if let Some(stderr) = child.stderr.take() {
+        tokio::spawn(pump(stderr, file));
+    }
+
+    Ok(())
+}
+
+/// Drain `stream` until the host closes it. Lines are kept even if they are not
+/// valid utf-8, and dropped once the log cannot be written, the host would
+/// otherwise block on a full pipe or die of a broken one.
+async fn pump(stream: impl AsyncRead + Unpin, file: Arc<Mutex<RotatingFile>>) {
+    let mut reader = BufReader::new(stream);
+    let mut line = Vec::new();
+    let mut failed = false;
+    loop {
+        line.clear();
+        match reader.read_until(b'\n', &mut line).await {
+            Ok(0) => break,
+            Ok(_) => {}
+            Err(e) => {
+                error!("Failed to read host output: {}", e);
+                break;
+            }
+        }
+        if failed {
+            continue;
+        }
+
+        let text = String::from_utf8_lossy(&line);
+        let text = text.trim_end_matches(['\n', '\r']);
+        if let Err(e) = file.lock().await.write_line(text).await {
+            error!("Failed to write host log, discarding host output: {}", e);
+            failed = true;
+        }
+    }
+}
+
+struct RotatingFile {
+    path: PathBuf,
+    file: File,
+    size: u64,
+}
+
+impl RotatingFile {
+    async fn open(path: PathBuf) -> Result<Self> {
+        if let Some(dir) = path.parent() {
+            tokio::fs::create_dir_all(dir).await?;
+        }
+
+        let file = OpenOptions::new().create(true).append(true).open(&path).await?;
+        let size = file.metadata().await?.len();
+        Ok(Self { path, file, size })
+    }
+
+    async fn write_line(&mut self, line: &str) -> Result<()> {
+        if self.size + line.len() as u64 + 1 > MAX_SIZE {
+            self.rotate().await?;
+        }
+
+        self.file.write_all(line.as_bytes()).await?;
+        self.file.write_all(b"\n").await?;
+        self.file.flush().await?;
+        self.size += line.len() as u64 + 1;
+        Ok(())
+    }
+
+    /// Shift `log.1` to `log.2` and so on, dropping the oldest, and start over
+    /// with an empty file.
+    async fn rotate(&mut self) -> Result<()> {
+        for n in (1..KEEP).rev() {
+            let from = rotated(&self.path, n);
+            if tokio::fs::try_exists(&from).await? {
+                tokio::fs::rename(&from, rotated(&self.path, n + 1)).await?;
+            }
+        }
+        tokio::fs::rename(&self.path, rotated(&self.path, 1)).await?;
+
+        self.file = File::create(&self.path).await?;
+        self.size = 0;
+        Ok(())
+    }
+}
+
+fn rotated(path: &Path, n: usize) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(format!(".{}", n));
+    PathBuf::from(name)
+}
@@ -0,0 +1,305 @@
+//! Full-text index of every chat on the host, kept on disk so chats from
+//! earlier sessions can be found without fetching them again.
+
+use std::{
+    collections::HashSet,
+    fmt,
+    ops::Range,
+    path::Path,
+    sync::{Arc, Mutex},
+};
+
+use anyhow::Result;
+use tantivy::{
+    collector::{DocSetCollector, TopDocs},
+    directory::MmapDirectory,
+    query::{AllQuery, QueryParser},
+    schema::{Field, Schema, Value, STORED, STRING, TEXT},
+    snippet::SnippetGenerator,
+    Index,
+    IndexReader,
+    IndexWriter,
+    ReloadPolicy,
+    TantivyDocument,
+    Term,
+};
+use tokio::sync::mpsc;
+use tracing::{error, info, warn};
+
+use crate::{
+    client::{ChatClient, ChatInfo},
+    message::MessageFrame,
+};
+
+/// Memory the writer may use before flushing a segment, tantivy's minimum.
+const WRITER_MEMORY: usize = 15_000_000;
+/// Chats fetched from the host between two commits of the first sync.
+const SYNC_BATCH: usize = 20;
+const SNIPPET_CHARS: usize = 120;
+
+#[derive(Debug, Clone, Copy)]
+struct Fields {
+    id: Field,
+    title: Field,
+    created_at: Field,
+    body: Field,
+}
+
+impl Fields {
+    fn schema() -> (Schema, Self) {
+        let mut builder = Schema::builder();
+        let fields = Self {
+            id: builder.add_text_field("id", STRING | STORED),
+            title: builder.add_text_field("title", TEXT | STORED),
+            created_at: builder.add_text_field("created_at", STORED),
+            body: builder.add_text_field("body", TEXT | STORED),
+        };
+
+        (builder.build(), fields)
+    }
+}
+
+/// A chat matching a search, with the best matching part of its messages.
+#[derive(Debug, Clone)]
+pub struct ChatHit {
+    pub chat: ChatInfo,
+    pub snippet: String,
+    /// Byte ranges of the matched words in `snippet`.
+    pub highlights: Vec<Range<usize>>,
+}
+
+/// Handle to the index, shared by the tui searching it and the main loop
+/// updating it.
+#[derive(Clone)]
+pub struct ChatIndex {
+    index: Index,
+    reader: IndexReader,
+    /// `None` when another instance holds the lock, the index is then only
+    /// searched.
+    writer: Option<Arc<Mutex<IndexWriter>>>,
+    fields: Fields,
+}
+
+impl fmt::Debug for ChatIndex {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ChatIndex")
+            .field("writable", &self.writer.is_some())
+            .finish_non_exhaustive()
+    }
+}
+
+impl ChatIndex {
+    /// Open the index in `dir`, building an empty one if there is none or it
+    /// cannot be read anymore.
+    pub fn open(dir: &Path) -> Result<Self> {
+        let (schema, fields) = Fields::schema();
+        std::fs::create_dir_all(dir)?;
+
+        let index = match Index::open_or_create(MmapDirectory::open(dir)?, schema.clone()) {
+            Ok(index) => index,
+            Err(e) => {
+                warn!("Rebuilding chat index: {}", e);
+                std::fs::remove_dir_all(dir)?;
+                std::fs::create_dir_all(dir)?;
+                Index::create_in_dir(dir, schema)?
+            }
+        };
+
+        let writer = match index.writer_with_num_threads(1, WRITER_MEMORY) {
+            Ok(writer) => Some(Arc::new(Mutex::new(writer))),
+            Err(e) => {
+                warn!("Chat index is read only: {}", e);
+                None
+            }
+        };
+        let reader = index.reader_builder()
+            .reload_policy(ReloadPolicy::Manual)
+            .try_into()?;
+
+        Ok(Self { index, reader, writer, fields })
+    }
+
+    /// Start the task indexing chats handed to the returned updater, one
+    /// after the other.
+    pub fn updater(&self) -> IndexUpdater {
+        let (tx, mut rx) = mpsc::unbounded_channel::<(ChatInfo, String)>();
+        let index = self.clone();
+        tokio::spawn(async move {
+            while let Some((chat, body)) = rx.recv().await {
+                let id = chat.id.clone();
+                let indexed = index.write_blocking(move |index, writer| {
+                    index.replace(writer, &chat, &body);
+                    Ok(())
+                });
+                if let Err(e) = indexed.await {
+                    error!("Failed to index chat {}: {}", id, e);
+                }
+            }
+        });
+
+        IndexUpdater(tx)
+    }
+
+    /// Run `f` with the writer and make its changes visible to searches.
+    fn write(&self, f: impl FnOnce(&mut IndexWriter) -> Result<()>) -> Result<()> {
+        let Some(writer) = &self.writer else {
+            return Ok(());
+        };
+
+        let mut writer = writer.lock().map_err(|_| anyhow::anyhow!("chat index writer poisoned"))?;
+        f(&mut writer)?;
+        writer.commit()?;
+        self.reader.reload()?;
+        Ok(())
+    }
+
+    /// [`Self::write`] on the blocking pool, a commit syncs the index to disk.
+    async fn write_blocking(&self, f: impl FnOnce(&ChatIndex, &mut IndexWriter) -> Result<()> + Send + 'static) -> Result<()> {
+        let index = self.clone();
+        tokio::task::spawn_blocking(move || index.write(|writer| f(&index, writer))).await?
+    }
+
+    fn replace(&self, writer: &mut IndexWriter, chat: &ChatInfo, body: &str) {
+        let mut doc = TantivyDocument::default();
+        doc.add_text(self.fields.id, &chat.id);
+        doc.add_text(self.fields.title, &chat.title);
+        if let Some(created_at) = &chat.created_at {
+            doc.add_text(self.fields.created_at, created_at);
+        }
+        doc.add_text(self.fields.body, body);
+
+        writer.delete_term(Term::from_field_text(self.fields.id, &chat.id));
+        if let Err(e) = writer.add_document(doc) {
+            error!("Failed to index chat {}: {}", chat.id, e);
+        }
+    }
+
+    /// Ids of every indexed chat.
+    fn ids(&self) -> Result<HashSet<String>> {
+        let searcher = self.reader.searcher();
+        let addresses = searcher.search(&AllQuery, &DocSetCollector)?;
+
+        let mut ids = HashSet::with_capacity(addresses.len());
+        for address in addresses {
+            let doc: TantivyDocument = searcher.doc(address)?;
+            if let Some(id) = doc.get_first(self.fields.id).and_then(|v| v.as_str()) {
+                ids.insert(id.to_string());
+            }
+        }
+
+        Ok(ids)
+    }
+
+    /// The best `limit` chats for `query`, a title match weighing more than
+    /// one in the messages.
+    ///
+    /// The query syntax is tantivy's, but a malformed query is still searched
+    /// as far as it can be read.
+    pub fn search(&self, query: &str, limit: usize) -> Result<Vec<ChatHit>> {
+        if query.trim().is_empty() {
+            return Ok(vec![]);
+        }
+
+        let searcher = self.reader.searcher();
+        let mut parser = QueryParser::for_index(&self.index, vec![self.fields.title, self.fields.body]);
+        parser.set_field_boost(self.fields.title, 2.0);
+        let (query, _) = parser.parse_query_lenient(query);
+
+        let mut snippets = SnippetGenerator::create(&searcher, &*query, self.fields.body)?;
+        snippets.set_max_num_chars(SNIPPET_CHARS);
+
+        let mut hits = vec![];
+        for (_, address) in searcher.search(&query, &TopDocs::with_limit(limit))? {
+            let doc: TantivyDocument = searcher.doc(address)?;
+            let text = |field| doc.get_first(field).and_then(|v| v.as_str()).map(str::to_string);
+
+            let snippet = snippets.snippet_from_doc(&doc);
+            hits.push(ChatHit {
+                chat: ChatInfo {
+                    id: text(self.fields.id).unwrap_or_default(),
+                    title: text(self.fields.title).unwrap_or_default(),
+                    created_at: text(self.fields.created_at),
+                },
+                // newlines are one byte like the space, the ranges stay valid
+                snippet: snippet.fragment().replace(['\n', '\r', '\t'], " "),
+                highlights: snippet.highlighted().to_vec(),
+            });
+        }
+
+        Ok(hits)
+    }
+}
+
+/// Index the chats of the host that are not indexed yet, and drop those
+/// deleted from it.
+///
+/// Chats changed in this session are updated as they are flushed, chats
+/// changed elsewhere when they are opened.
+pub async fn sync(index: ChatIndex, client: ChatClient) {
+    if index.writer.is_none() {
+        return;
+    }
+    if let Err(e) = try_sync(&index, &client).await {
+        error!("Failed to sync chat index: {}", e);
+    }
+}
+
+async fn try_sync(index: &ChatIndex, client: &ChatClient) -> Result<()> {
+    let chats = client.list_chats().await?;
+    let indexed = index.ids()?;
+
+    let listed: HashSet<&str> = chats.iter().map(|chat| chat.id.as_str()).collect();
+    let deleted: Vec<String> = indexed.iter().filter(|id| !listed.contains(String::as_str(id))).cloned().collect();
+    let missing: Vec<&ChatInfo> = chats.iter().filter(|chat| !indexed.contains(&chat.id)).collect();
+    info!("Chat index sync: {} to add, {} to drop", missing.len(), deleted.len());
+
+    if !deleted.is_empty() {
+        index.write_blocking(move |index, writer| {
+            for id in &deleted {
+                writer.delete_term(Term::from_field_text(index.fields.id, id));
+            }
+            Ok(())
+        }).await?;
+    }
+
+    for batch in missing.chunks(SYNC_BATCH) {
+        let mut details = Vec::with_capacity(batch.len());
+        for chat in batch {
+            match client.get_chat(&chat.id).await {
+                Ok(detail) => details.push((ChatInfo::clone(chat), body(&MessageFrame::from_messages(detail.messages)))),
+                Err(e) => warn!("Failed to fetch chat {} to index: {}", chat.id, e),
+            }
+        }
+
+        index.write_blocking(move |index, writer| {
+            for (chat, body) in &details {
+                index.replace(writer, chat, body);
+            }
+            Ok(())
+        }).await?;
+    }
+
+    Ok(())
+}
+
+/// Hands chats to the indexing task, so the caller never waits on the disk.
+#[derive(Debug, Clone)]
+pub struct IndexUpdater(mpsc::UnboundedSender<(ChatInfo, String)>);
+
+impl IndexUpdater {
+    /// Replace what is indexed for `chat` by its current messages.
+    pub fn update<'a>(&self, chat: &ChatInfo, frames: impl IntoIterator<Item = &'a MessageFrame>) {
+        if self.0.send((chat.clone(), body(frames))).is_err() {
+            warn!("Chat index task is gone, chat {} is not indexed", chat.id);
+        }
+    }
+}
+
+/// The text of every message of a chat, as it is searched.
+fn body<'a>(frames: impl IntoIterator<Item = &'a MessageFrame>) -> String {
+    frames.into_iter()
+        .flat_map(|frame| [frame.user.body.content.as_str(), frame.ai.body.content.as_str()])
+        .filter(|content| !content.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
@@ -3,7 +3,7 @@ use std::{path::PathBuf, sync::LazyLock};
 use anyhow::Result;
 use directories::ProjectDirs;
 use tracing_error::ErrorLayer;
-use tracing_subscriber::{self, layer::SubscriberExt, util::SubscriberInitExt, Layer};
+use tracing_subscriber::{self, filter::EnvFilter, layer::SubscriberExt, util::SubscriberInitExt, Layer};
 
 use crate::shared::PROJECT_NAME;
 
@@ -25,26 +25,31 @@ pub fn get_data_dir() -> PathBuf {
     }
 }
 
-pub fn initialize_logging() -> Result<()> {
+pub fn log_file() -> PathBuf {
+    get_data_dir().join(LOG_FILE.clone())
+}
+
+/// Start logging to the log file, `level` overrides the environment and may be
+/// a bare level or a full filter directive.
+pub fn initialize_logging(level: Option<&str>) -> Result<()> {
     let directory = get_data_dir();
     std::fs::create_dir_all(directory.clone())?;
-    let log_path = directory.join(LOG_FILE.clone());
+    let log_path = log_file();
     let log_file = std::fs::File::create(log_path)?;
-    unsafe {
-        std::env::set_var(
-        "RUST_LOG",
-        std::env::var("RUST_LOG")
+    let directive = match level {
+        Some(level) if level.contains('=') => level.to_string(),
+        Some(level) => format!("{}={}", env!("CARGO_CRATE_NAME"), level),
+        None => std::env::var("RUST_LOG")
             .or_else(|_| std::env::var(LOG_ENV.clone()))
             .unwrap_or_else(|_| format!("{}=info", env!("CARGO_CRATE_NAME"))),
-        );
-    }
+    };
     let file_subscriber = tracing_subscriber::fmt::layer()
         .with_file(true)
         .with_line_number(true)
         .with_writer(log_file)
         .with_target(false)
         .with_ansi(false)
-        .with_filter(tracing_subscriber::filter::EnvFilter::from_default_env());
+        .with_filter(EnvFilter::new(directive));
     tracing_subscriber::registry().with(file_subscriber).with(ErrorLayer::default()).init();
     Ok(())
 }
@@ -1,125 +1,410 @@
-use std::sync::Arc;
+use std::{collections::VecDeque, process::ExitCode, sync::Arc};
 
-use chat::ChatThread;
-use tracing::{error, info};
+use clap::Parser;
+use chat::{ChatRegistry, ChatWriter, ThreadKey};
+use tracing::{error, info, warn};
 use anyhow::Result;
-use client::ChatResponse;
+use client::{ChatClient, ChatResponse, ChatResponseStream};
 use futures::StreamExt;
-use host::{HostEvent, HostListen, HostServer};
-use shared::{UIAction, UIActionResult};
+use cli::{Cli, Command, HostCommand};
+use host::{HostConnection, HostTarget};
+use index::{ChatIndex, IndexUpdater};
+use message::MessageFrame;
+use shared::{UIAction, UIActionResult, PROJECT_DIRS};
+use supervisor::HostHealth;
 use tokio::{signal, sync::mpsc};
 
 mod chat;
+mod cli;
 mod client;
+mod clipboard;
+mod headless;
 mod host;
+mod host_log;
+mod index;
 mod logger;
+mod mcp_config;
 mod message;
+mod model_config;
+mod provision;
 mod shared;
+mod sse;
+mod supervisor;
 mod tui;
 mod widget;
 
 #[tokio::main]
-async fn main() -> Result<()> {
-    logger::initialize_logging()?;
+async fn main() -> Result<ExitCode> {
+    let cli = Cli::parse();
+    if let Some(config_dir) = cli.config_dir {
+        shared::set_config_dir(config_dir);
+    }
+    logger::initialize_logging(cli.log_level.as_deref())?;
 
-    let (mut host, mut host_recv) = host::HostProcess::new().await?;
-    host.spawn().await?;
+    let target = cli.host.target();
+    match cli.command {
+        None | Some(Command::Tui) => {
+            run_tui(target).await?;
+            Ok(ExitCode::SUCCESS)
+        }
+        Some(Command::Ask(args)) => headless::ask(target, args).await,
+        Some(Command::Chats(args)) => headless::chats(target, args).await,
+        Some(Command::Config) => Ok(headless::config(&target)),
+        Some(Command::Host(HostCommand::Status)) => headless::host_status(target).await,
+        Some(Command::Host(HostCommand::Install)) => headless::host_install(target).await,
+    }
+}
 
+async fn run_tui(target: HostTarget) -> Result<()> {
     let (tx_ui, mut rx_ui) = mpsc::channel(1);
     let (tx_host, rx_host) = mpsc::channel(1);
 
-    let chat_thread = ChatThread::default();
-    let (mut chat_writer, chat_reader) = chat_thread.split();
+    let (mut chat_writer, chat_reader) = ChatRegistry::default().split();
+    let index = ChatIndex::open(&PROJECT_DIRS.chat_index_dir())
+        .inspect_err(|e| error!("Failed to open chat index: {}", e))
+        .ok();
 
+    let tui_index = index.clone();
     let tui_handle = tokio::spawn(async move {
-        let tui = tui::Tui::new(tx_ui, rx_host);
+        let tui = tui::Tui::new(tx_ui, rx_host, tui_index);
         tui.run(chat_reader).await;
     });
 
-    // wait for host to return ip and port
-    let (ip, port) = tokio::select! {
-        _ = signal::ctrl_c() => return Ok(()),
-        Some(evt) = host_recv.recv() => {
-            match evt {
-                HostEvent::BusMessage(msg) => {
-                    if let Some(HostServer {
-                        listen: Some(HostListen {
-                            ip: Some(_ip),
-                            port: Some(_port),
-                        }),
-                    }) = msg.server
-                    {
-                        info!("Host listen: {}:{}", _ip, _port);
-                        (_ip, _port)
-                    } else {
-                        error!("Failed to get host listen: {:?}", msg);
-                        return Err(anyhow::anyhow!("Failed to get host listen"));
-                    }
-                },
-                HostEvent::Error(e) => {
-                    return Err(anyhow::anyhow!(e));
-                }
-            }
-        }
+    // spawn or attach to the host and make sure it is running
+    let Some(HostConnection { process, events, listen_message, client }) = host::connect(target).await? else {
+        return Ok(());
     };
+    tx_host.send(UIActionResult::Host(HostHealth::Up { endpoint: client.endpoint(), error: None })).await?;
 
-    // make sure host is running
-    let client = client::ChatClient::new(ip, port);
-    client.wait_for_server().await?;
+    // follow the status bus and restart the host if it dies under us
+    let supervisor = match (process, events) {
+        (Some(process), Some(events)) => Some(supervisor::supervise(process, events, listen_message, client.clone(), tx_host.clone())),
+        _ => None,
+    };
+
+    if let Some(index) = &index {
+        tokio::spawn(index::sync(index.clone(), client.clone()));
+    }
+    let updater = index.as_ref().map(ChatIndex::updater);
+
+    match client.model_config().await {
+        Ok(config) => tx_host.send(UIActionResult::ActiveModel(config.active_model())).await?,
+        Err(e) => warn!("Failed to read the active model: {}", e),
+    }
 
     // main loop
+    let mut deferred = VecDeque::new();
     loop {
-        tokio::select! {
-            _ = signal::ctrl_c() => break,
-            Some(evt) = rx_ui.recv() => {
-                match evt {
-                    UIAction::Quit => break,
-                    UIAction::Chat { id, message } => {
-                        info!("Chat: {:?}", id);
-                        let mut stream = client.chat_stream(&message, id.as_deref().map(|s| s.as_str()));
-                        let mut chat_id: Option<Arc<String>> = id;
-                        chat_writer.mut_user_message().content = message;
-
-                        use ChatResponse::*;
-                        while let Some(Ok(response)) = stream.next().await {
-                            match response {
-                                Text(text)=> {
-                                    if let Some(id) = &chat_id {
-                                        chat_writer.mut_ai_message().content.push_str(&text);
-                                        tx_host.send(UIActionResult::Chat {
-                                            id: id.clone(),
-                                            content: text,
-                                        }).await?;
-                                    }
-                                },
-                                ChatInfo(chat_info) => {
-                                    if chat_id.is_none() {
-                                        chat_writer.mut_user_message().chat_id = chat_info.id.clone();
-                                        chat_id = Some(Arc::new(chat_info.id));
-                                    }
-                                },
-                                MessageInfo(message_info) => {
-                                    let crate::client::MessageInfo { user_message_id , assistant_message_id } = message_info;
-                                    chat_writer.mut_user_message().message_id = user_message_id;
-                                    chat_writer.mut_ai_message().message_id = assistant_message_id;
-                                },
-                                ToolCalls(tool_calls) => {
-
-                                },
-                                ToolResult(tool_results) => {
-
-                                },
+        // actions that came in while an answer was streaming go first
+        let evt = match deferred.pop_front() {
+            Some(evt) => evt,
+            None => tokio::select! {
+                _ = signal::ctrl_c() => break,
+                Some(evt) = rx_ui.recv() => evt,
+            },
+        };
+
+        match evt {
+            UIAction::Quit => break,
+            UIAction::ListChats => {
+                let chats = client.list_chats().await.unwrap_or_else(|e| {
+                    error!("Failed to list chats: {}", e);
+                    vec![]
+                });
+                tx_host.send(UIActionResult::ChatList(chats)).await?;
+            }
+            UIAction::LoadChat { key, id } => {
+                info!("Load chat: {}", id);
+                let loaded = match client.get_chat(&id).await {
+                    Ok(detail) => {
+                        let frames = MessageFrame::from_messages(detail.messages);
+                        if let Some(updater) = &updater {
+                            updater.update(&detail.chat, &frames);
+                        }
+                        chat_writer.load(key, detail.chat, frames).await?;
+                        Ok(())
+                    }
+                    Err(e) => {
+                        error!("Failed to load chat {}: {}", id, e);
+                        Err(e.to_string())
+                    }
+                };
+                tx_host.send(UIActionResult::ChatLoaded { key, loaded }).await?;
+            }
+            UIAction::LoadModelConfig => {
+                let config = client.model_config().await.map_err(|e| {
+                    error!("Failed to load model config: {}", e);
+                    e.to_string()
+                });
+                if let Ok(config) = &config {
+                    tx_host.send(UIActionResult::ActiveModel(config.active_model())).await?;
+                }
+                tx_host.send(UIActionResult::ModelConfig(config)).await?;
+            }
+            UIAction::SaveModelConfig(config) => {
+                info!("Save model config, active provider: {}", config.active_provider);
+                let saved = client.replace_model_config(&config).await.map_err(|e| {
+                    error!("Failed to save model config: {}", e);
+                    e.to_string()
+                });
+                if saved.is_ok() {
+                    tx_host.send(UIActionResult::ActiveModel(config.active_model())).await?;
+                }
+                tx_host.send(UIActionResult::ModelConfigSaved(saved)).await?;
+            }
+            UIAction::ListModels(configs) => {
+                // providers may be slow or unreachable, do not hold the loop
+                for (provider, config) in configs {
+                    let client = client.clone();
+                    let tx_host = tx_host.clone();
+                    tokio::spawn(async move {
+                        match client.provider_models(&config).await {
+                            Ok(models) => {
+                                let _ = tx_host.send(UIActionResult::ProviderModels { provider, models }).await;
                             }
+                            Err(e) => warn!("Failed to list the models of {}: {}", provider, e),
                         }
+                    });
+                }
+            }
+            UIAction::LoadMcp => {
+                let config = client.mcp_config().await.map_err(|e| {
+                    error!("Failed to load mcp config: {}", e);
+                    e.to_string()
+                });
+                tx_host.send(UIActionResult::McpConfig(config)).await?;
+                send_mcp_servers(&client, &tx_host).await?;
+            }
+            UIAction::SaveMcpConfig(config) => {
+                info!("Save mcp config: {} servers", config.mcp_servers.len());
+                let saved = client.replace_mcp_config(&config).await.map_err(|e| {
+                    error!("Failed to save mcp config: {}", e);
+                    e.to_string()
+                });
+                let reload = saved.is_ok();
+                tx_host.send(UIActionResult::McpSaved(saved)).await?;
+                if reload {
+                    send_mcp_servers(&client, &tx_host).await?;
+                }
+            }
+            UIAction::Chat { key, message } => {
+                let id = chat_writer.select(key).await?.map(Arc::new);
+                info!("Chat: {:?}", id);
+                let stream = client.chat_stream(&message, id.as_deref().map(|s| s.as_str()));
+                chat_writer.mut_user_message().content = message;
 
-                        chat_writer.flush().await?;
+                let cx = StreamContext { client: &client, updater: updater.as_ref(), chat_writer: &mut chat_writer, tx_host: &tx_host, rx_ui: &mut rx_ui, deferred: &mut deferred };
+                if run_stream(stream, key, id, cx).await? {
+                    break;
+                }
+            }
+            UIAction::Retry { key, message_id } => {
+                info!("Retry: {}", message_id);
+                let id = match chat_writer.retry(key, &message_id).await {
+                    Ok(id) => id,
+                    Err(e) => {
+                        error!("Failed to retry {}: {}", message_id, e);
+                        tx_host.send(UIActionResult::Error { key, message: e.to_string() }).await?;
                         tx_host.send(UIActionResult::End).await?;
+                        continue;
                     }
+                };
+                let stream = client.retry_stream(&id, &message_id);
+
+                let cx = StreamContext { client: &client, updater: updater.as_ref(), chat_writer: &mut chat_writer, tx_host: &tx_host, rx_ui: &mut rx_ui, deferred: &mut deferred };
+                if run_stream(stream, key, Some(Arc::new(id)), cx).await? {
+                    break;
+                }
+            }
+            UIAction::Edit { key, message_id, message } => {
+                info!("Edit: {}", message_id);
+                let id = match chat_writer.edit(key, &message_id, message.clone()).await {
+                    Ok(id) => id,
+                    Err(e) => {
+                        error!("Failed to edit {}: {}", message_id, e);
+                        tx_host.send(UIActionResult::Error { key, message: e.to_string() }).await?;
+                        tx_host.send(UIActionResult::End).await?;
+                        continue;
+                    }
+                };
+                let stream = client.edit_stream(&id, &message_id, &message);
+
+                let cx = StreamContext { client: &client, updater: updater.as_ref(), chat_writer: &mut chat_writer, tx_host: &tx_host, rx_ui: &mut rx_ui, deferred: &mut deferred };
+                if run_stream(stream, key, Some(Arc::new(id)), cx).await? {
+                    break;
                 }
             }
+            UIAction::Abort => {}
         }
     }
 
+    // stop the host before the tui gives back the terminal
+    if let Some(supervisor) = supervisor {
+        supervisor.abort();
+        let _ = supervisor.await;
+    }
+
     tui_handle.await?;
     Ok(())
 }
+
+/// What a chat stream reads from and writes to.
+struct StreamContext<'a> {
+    client: &'a ChatClient,
+    updater: Option<&'a IndexUpdater>,
+    chat_writer: &'a mut ChatWriter,
+    tx_host: &'a mpsc::Sender<UIActionResult>,
+    rx_ui: &'a mut mpsc::Receiver<UIAction>,
+    /// Ui actions that came in while streaming, handled once it ends.
+    deferred: &'a mut VecDeque<UIAction>,
+}
+
+/// Stream an answer into the selected thread, flush it and index it, aborting
+/// it on the host if the user stopped it. Returns whether the user asked to
+/// quit.
+async fn run_stream(
+    stream: ChatResponseStream,
+    key: ThreadKey,
+    id: Option<Arc<String>>,
+    mut cx: StreamContext<'_>,
+) -> Result<bool> {
+    let end = stream_chat(stream, key, id, &mut cx).await?;
+    if let StreamEnd::Aborted(chat_id) | StreamEnd::Quit(chat_id) = &end {
+        info!("Abort chat: {:?}", chat_id);
+        if let Some(chat_id) = chat_id
+            && let Err(e) = cx.client.abort(chat_id).await
+        {
+            error!("Failed to abort chat {}: {}", chat_id, e);
+        }
+        cx.chat_writer.interrupt();
+    }
+
+    cx.chat_writer.flush().await?;
+    cx.tx_host.send(UIActionResult::End).await?;
+
+    if let Some(updater) = cx.updater
+        && let Some(thread) = cx.chat_writer.thread()
+    {
+        let thread = thread.lock().await;
+        if let Some(info) = thread.info() {
+            updater.update(&info, thread.messages.iter().map(|frame| &**frame));
+        }
+    }
+    Ok(matches!(end, StreamEnd::Quit(_)))
+}
+
+/// Tell the tui which mcp servers the host runs.
+async fn send_mcp_servers(client: &ChatClient, tx_host: &mpsc::Sender<UIActionResult>) -> Result<()> {
+    let servers = client.list_tools().await.map_err(|e| {
+        error!("Failed to list mcp tools: {}", e);
+        e.to_string()
+    });
+    tx_host.send(UIActionResult::McpServers(servers)).await?;
+    Ok(())
+}
+
+/// How a chat stream ended.
+enum StreamEnd {
+    Done,
+    Aborted(Option<Arc<String>>),
+    Quit(Option<Arc<String>>),
+}
+
+/// Forward a chat stream into the chat writer and the tui, until it ends or
+/// the user asks to stop it.
+async fn stream_chat(
+    mut stream: ChatResponseStream,
+    key: ThreadKey,
+    mut chat_id: Option<Arc<String>>,
+    cx: &mut StreamContext<'_>,
+) -> Result<StreamEnd> {
+    use ChatResponse::*;
+    let StreamContext { chat_writer, tx_host, rx_ui, deferred, .. } = cx;
+    loop {
+        let response = tokio::select! {
+            response = stream.next() => response,
+            Some(evt) = rx_ui.recv() => {
+                match evt {
+                    UIAction::Abort => return Ok(StreamEnd::Aborted(chat_id)),
+                    UIAction::Quit => return Ok(StreamEnd::Quit(chat_id)),
+                    UIAction::Chat { .. } | UIAction::Retry { .. } | UIAction::Edit { .. } => {
+                        warn!("Ignore chat while streaming");
+                        continue;
+                    }
+                    evt => {
+                        deferred.push_back(evt);
+                        continue;
+                    }
+                }
+            }
+        };
+
+        let response = match response {
+            None => return Ok(StreamEnd::Done),
+            Some(Ok(response)) => response,
+            Some(Err(e)) => {
+                error!("Chat stream failed: {}", e);
+                let message = e.to_string();
+                chat_writer.fail(message.clone());
+                tx_host.send(UIActionResult::Error { key, message }).await?;
+                return Ok(StreamEnd::Done);
+            }
+        };
+
+        match response {
+            Text(text)=> {
+                if chat_id.is_some() {
+                    chat_writer.mut_ai_message().content.push_str(&text);
+                    tx_host.send(UIActionResult::Chat {
+                        key,
+                        content: text,
+                    }).await?;
+                }
+            },
+            ChatInfo(chat_info) => {
+                if chat_id.is_none() {
+                    chat_writer.mut_user_message().chat_id = chat_info.id.clone();
+                    chat_id = Some(Arc::new(chat_info.id.clone()));
+                }
+                chat_writer.set_info(chat_info).await;
+            },
+            MessageInfo(message_info) => {
+                let crate::client::MessageInfo { user_message_id , assistant_message_id } = message_info;
+                chat_writer.mut_user_message().message_id = user_message_id;
+                chat_writer.mut_ai_message().message_id = assistant_message_id;
+            },
+            ToolCalls(tool_calls) => {
+                for tool_call in tool_calls {
+                    let arguments = tool_call.pretty_arguments();
+                    chat_writer.push_tool_call(tool_call.name.clone(), arguments.clone());
+                    if chat_id.is_some() {
+                        tx_host.send(UIActionResult::ToolCall {
+                            key,
+                            name: tool_call.name,
+                            arguments,
+                        }).await?;
+                    }
+                }
+            },
+            ToolResult(tool_results) => {
+                for tool_result in tool_results {
+                    let result = tool_result.pretty_result();
+                    chat_writer.set_tool_result(&tool_result.name, result.clone());
+                    if chat_id.is_some() {
+                        tx_host.send(UIActionResult::ToolResult {
+                            key,
+                            name: tool_result.name,
+                            result,
+                        }).await?;
+                    }
+                }
+            },
+            Error(error) => {
+                let message = client::error_message(&error);
+                error!("Host reported an error: {}", message);
+                chat_writer.fail(message.clone());
+                tx_host.send(UIActionResult::Error { key, message }).await?;
+            },
+        }
+    }
+}
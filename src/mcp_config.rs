@@ -0,0 +1,186 @@
+//! Typed view of the host's `mcp_config.json` and of the tools its servers
+//! expose.
+
+use std::collections::BTreeMap;
+
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub struct McpConfig {
+    /// Every configured server by name.
+    #[serde(rename = "mcpServers", default)]
+    pub mcp_servers: BTreeMap<String, McpServerConfig>,
+    /// Settings tive does not edit, written back untouched.
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Transport {
+    /// A local process spoken to over its stdin and stdout.
+    #[default]
+    Stdio,
+    Sse,
+    Websocket,
+    Streamable,
+}
+
+impl Transport {
+    pub fn name(self) -> &'static str {
+        match self {
+            Transport::Stdio => "stdio",
+            Transport::Sse => "sse",
+            Transport::Websocket => "websocket",
+            Transport::Streamable => "streamable",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub struct McpServerConfig {
+    #[serde(default)]
+    pub transport: Transport,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub command: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub args: Vec<String>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub env: BTreeMap<String, String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    /// Tools of the server hidden from the model.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub exclude_tools: Vec<String>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+impl McpServerConfig {
+    /// Everything preventing the host from starting this server.
+    pub fn validate(&self) -> Vec<String> {
+        let mut errors = vec![];
+        match self.transport {
+            Transport::Stdio => {
+                if self.command.as_deref().is_none_or(|command| command.trim().is_empty()) {
+                    errors.push("a stdio server needs a command".to_string());
+                }
+            }
+            transport => match self.url.as_deref().map(Url::parse) {
+                None => errors.push(format!("a {} server needs a url", transport.name())),
+                Some(Ok(_)) => {}
+                Some(Err(e)) => errors.push(format!("url: {}", e)),
+            },
+        }
+        errors
+    }
+
+    /// Hide the tool `name` from the model, or show it again.
+    pub fn toggle_tool(&mut self, name: &str) {
+        match self.exclude_tools.iter().position(|tool| tool == name) {
+            Some(i) => {
+                self.exclude_tools.remove(i);
+            }
+            None => self.exclude_tools.push(name.to_string()),
+        }
+    }
+}
+
+/// A server as the host runs it, with the tools it exposes.
+#[derive(Debug, Clone, Deserialize)]
+pub struct McpServerStatus {
+    pub name: String,
+    #[serde(default)]
+    pub tools: Vec<McpTool>,
+    #[serde(default)]
+    pub description: String,
+    /// Why the host could not start the server.
+    #[serde(default)]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct McpTool {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn server(transport: Transport) -> McpServerConfig {
+        McpServerConfig { transport, ..Default::default() }
+    }
+
+    #[test]
+    fn stdio_needs_a_command() {
+        for command in [None, Some(""), Some("  ")] {
+            let config = McpServerConfig { command: command.map(str::to_string), ..server(Transport::Stdio) };
+            assert_eq!(config.validate(), vec!["a stdio server needs a command"]);
+        }
+        let config = McpServerConfig { command: Some("uvx".to_string()), ..server(Transport::Stdio) };
+        assert!(config.validate().is_empty());
+    }
+
+    #[test]
+    fn url_servers_need_a_url() {
+        for transport in [Transport::Sse, Transport::Websocket, Transport::Streamable] {
+            let missing = format!("a {} server needs a url", transport.name());
+            assert_eq!(server(transport).validate(), vec![missing]);
+
+            let config = McpServerConfig { url: Some("not a url".to_string()), ..server(transport) };
+            assert_eq!(config.validate(), vec!["url: relative URL without a base"]);
+
+            let url = Some("http://localhost:9000/sse".to_string());
+            let config = McpServerConfig { url, ..server(transport) };
+            assert!(config.validate().is_empty());
+        }
+    }
+
+    #[test]
+    fn toggle_tool_adds_then_removes() {
+        let mut config = server(Transport::Stdio);
+        config.toggle_tool("fetch");
+        assert_eq!(config.exclude_tools, vec!["fetch"]);
+        config.toggle_tool("fetch");
+        assert!(config.exclude_tools.is_empty());
+    }
+
+    #[test]
+    fn round_trip_keeps_unknown_settings() {
+        let json = serde_json::json!({
+            "mcpServers": {
+                "fetch": {
+                    "transport": "stdio",
+                    "command": "uvx",
+                    "args": ["mcp-server-fetch"],
+                    "env": {"PROXY": "none"},
+                    "enabled": false,
+                    "exclude_tools": ["fetch_raw"],
+                    "initialTimeout": 30,
+                },
+                "echo": {
+                    "transport": "sse",
+                    "url": "http://localhost:9000/sse",
+                    "enabled": true,
+                    "headers": {"X-Token": "t"},
+                },
+            },
+            "note": "keep",
+        });
+        let config: McpConfig = serde_json::from_value(json.clone()).unwrap();
+        assert_eq!(config.extra["note"], "keep");
+        assert_eq!(config.mcp_servers["fetch"].extra["initialTimeout"], 30);
+        assert_eq!(serde_json::to_value(&config).unwrap(), json);
+    }
+}
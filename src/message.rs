@@ -1,4 +1,4 @@
-use std::ops::Deref;
+use std::{ops::Deref, sync::Arc};
 
 use chrono::{SecondsFormat, Utc};
 use serde::Deserialize;
@@ -42,12 +42,16 @@ pub enum Message {
     UserMessage(UserMessage),
     #[serde(rename = "tool_result")]
     ToolCallResult(ToolCallResult),
+    #[serde(other)]
+    Other,
 }
 
 #[derive(Debug, Deserialize, Clone)]
 pub struct ToolCall {
     pub name: String,
+    #[serde(default)]
     pub id: String,
+    #[serde(default)]
     pub args: Value,
 }
 
@@ -55,8 +59,9 @@ pub struct ToolCall {
 pub struct AIMessage {
     #[serde(flatten)]
     pub body: BaseMessage,
-    #[serde(rename = "toolCalls")]
+    #[serde(rename = "toolCalls", default)]
     pub tool_calls: Vec<ToolCall>,
+    #[serde(default)]
     pub files: Vec<String>,
 }
 
@@ -118,14 +123,115 @@ impl Deref for ToolCallResult {
     }
 }
 
+/// A tool the agent ran while generating an AI message.
+#[derive(Debug, Clone, Default)]
+pub struct ToolInvocation {
+    pub name: String,
+    /// pretty-printed JSON arguments
+    pub arguments: String,
+    pub result: Option<String>,
+    /// byte offset into the AI content where the call was issued
+    pub offset: usize,
+}
+
+impl ToolInvocation {
+    /// Attach a result to the first pending invocation of the tool `name`.
+    pub fn resolve(tools: &mut [ToolInvocation], name: &str, result: String) {
+        if let Some(tool) = tools.iter_mut().find(|t| t.name == name && t.result.is_none()) {
+            tool.result = Some(result);
+        }
+    }
+
+    /// Attach a result to the first pending invocation, whatever its name.
+    pub fn resolve_next(tools: &mut [ToolInvocation], result: String) {
+        if let Some(tool) = tools.iter_mut().find(|t| t.result.is_none()) {
+            tool.result = Some(result);
+        }
+    }
+}
+
 #[derive(Debug, Clone, Default)]
 pub struct MessageFrame {
     pub ai: AIMessage,
     pub user: UserMessage,
+    pub tools: Vec<ToolInvocation>,
+    /// the generation was aborted before the host finished it
+    pub interrupted: bool,
+    /// the host or the connection failed the generation
+    pub error: Option<String>,
+    /// earlier generations of this turn, oldest first
+    pub alternates: Vec<Arc<MessageFrame>>,
 }
 
 impl MessageFrame {
     pub fn split_ref(&self) -> (&UserMessage, &AIMessage) {
         (&self.user, &self.ai)
     }
+
+    /// Number of generations of this turn, the current one included.
+    #[inline]
+    pub fn generations(&self) -> usize {
+        self.alternates.len() + 1
+    }
+
+    /// The earlier generation `n`, or the current one.
+    pub fn generation(&self, n: Option<usize>) -> &MessageFrame {
+        n.and_then(|n| self.alternates.get(n)).map_or(self, |frame| frame)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.user.body.content.is_empty() && self.ai.body.content.is_empty() && self.tools.is_empty()
+    }
+
+    pub fn push_tool_call(&mut self, name: String, arguments: String) {
+        let offset = self.ai.body.content.len();
+        self.tools.push(ToolInvocation { name, arguments, result: None, offset });
+    }
+
+    pub fn set_tool_result(&mut self, name: &str, result: String) {
+        ToolInvocation::resolve(&mut self.tools, name, result);
+    }
+
+    /// Rebuild frames from a chat history as stored by the host.
+    ///
+    /// The host keeps one record per message, so a single turn may be split
+    /// into several assistant records with tool results in between.
+    pub fn from_messages(messages: Vec<Message>) -> Vec<MessageFrame> {
+        let mut frames: Vec<MessageFrame> = vec![];
+
+        for message in messages {
+            match message {
+                Message::UserMessage(user) => frames.push(MessageFrame {
+                    user,
+                    ..Default::default()
+                }),
+                Message::AIMessage(ai) => {
+                    let Some(frame) = frames.last_mut() else {
+                        continue;
+                    };
+
+                    if frame.ai.body.content.is_empty() && frame.ai.tool_calls.is_empty() {
+                        frame.ai.body = ai.body.clone();
+                        frame.ai.body.content.clear();
+                    }
+
+                    frame.ai.body.content.push_str(&ai.body.content);
+                    for call in ai.tool_calls {
+                        let arguments = serde_json::to_string_pretty(&call.args).unwrap_or_default();
+                        frame.push_tool_call(call.name.clone(), arguments);
+                        frame.ai.tool_calls.push(call);
+                    }
+                    frame.ai.files.extend(ai.files);
+                },
+                Message::ToolCallResult(result) => {
+                    if let Some(frame) = frames.last_mut() {
+                        ToolInvocation::resolve_next(&mut frame.tools, result.body.content);
+                    }
+                },
+                Message::Other => {},
+            }
+        }
+
+        frames
+    }
 }
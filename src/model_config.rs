@@ -0,0 +1,250 @@
+//! Typed view of the host's `model_config.json`, checked before it is saved
+//! so a typo cannot leave the host without a usable model.
+
+use std::collections::BTreeMap;
+
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+/// Provider the host falls back to, answering without any model.
+pub const FAKE_PROVIDER: &str = "fake";
+
+/// Model providers the host knows, as `modelProvider` names them.
+pub const MODEL_PROVIDERS: &[&str] = &[
+    "openai",
+    "openai_compatible",
+    "anthropic",
+    "ollama",
+    "google-genai",
+    "mistralai",
+    "bedrock",
+    "deepseek",
+    "azure_openai",
+];
+
+/// Providers that answer without an api key.
+const KEYLESS_PROVIDERS: &[&str] = &["ollama", "openai_compatible", "bedrock"];
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct ModelConfig {
+    /// Name of the entry of `configs` used for chats.
+    #[serde(rename = "activeProvider")]
+    pub active_provider: String,
+    #[serde(rename = "enableTools", default = "default_true")]
+    pub enable_tools: bool,
+    #[serde(rename = "disableDiveSystemPrompt", default)]
+    pub disable_dive_system_prompt: bool,
+    /// Every configured provider by name.
+    #[serde(default)]
+    pub configs: BTreeMap<String, ProviderConfig>,
+    /// Settings tive does not edit, written back untouched.
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+impl Default for ModelConfig {
+    fn default() -> Self {
+        Self {
+            active_provider: FAKE_PROVIDER.to_string(),
+            enable_tools: true,
+            disable_dive_system_prompt: false,
+            configs: BTreeMap::new(),
+            extra: Map::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub struct ProviderConfig {
+    #[serde(rename = "modelProvider")]
+    pub model_provider: String,
+    #[serde(default)]
+    pub model: String,
+    #[serde(rename = "apiKey", default, skip_serializing_if = "Option::is_none")]
+    pub api_key: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub configuration: Option<ProviderConnection>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub struct ProviderConnection {
+    #[serde(rename = "baseURL", default, skip_serializing_if = "Option::is_none")]
+    pub base_url: Option<String>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+impl ProviderConfig {
+    pub fn base_url(&self) -> Option<&str> {
+        self.configuration.as_ref()?.base_url.as_deref()
+    }
+
+    pub fn set_base_url(&mut self, base_url: Option<String>) {
+        self.configuration.get_or_insert_default().base_url = base_url;
+    }
+}
+
+fn default_true() -> bool {
+    true
+}
+
+/// What is wrong with a config, and where.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigError {
+    /// The provider the error is about, `None` for the general settings.
+    pub provider: Option<String>,
+    pub message: String,
+}
+
+impl ModelConfig {
+    /// The model answering chats, as shown to the user.
+    pub fn active_model(&self) -> String {
+        match self.configs.get(&self.active_provider) {
+            Some(provider) if !provider.model.is_empty() => provider.model.clone(),
+            _ => self.active_provider.clone(),
+        }
+    }
+
+    /// Everything preventing the host from using this config, empty if it
+    /// can be saved.
+    pub fn validate(&self) -> Vec<ConfigError> {
+        let mut errors = vec![];
+        let mut error = |provider: Option<&str>, message: String| errors.push(ConfigError {
+            provider: provider.map(str::to_string),
+            message,
+        });
+
+        if self.active_provider != FAKE_PROVIDER && !self.configs.contains_key(&self.active_provider) {
+            error(None, format!("active provider \"{}\" is not configured", self.active_provider));
+        }
+
+        for (name, provider) in &self.configs {
+            let at = Some(name.as_str());
+            if name.trim().is_empty() {
+                error(at, "the name is empty".to_string());
+            }
+            if !MODEL_PROVIDERS.contains(&provider.model_provider.as_str()) {
+                error(at, format!("unknown model provider \"{}\"", provider.model_provider));
+            }
+            if provider.model.trim().is_empty() {
+                error(at, "no model given".to_string());
+            }
+            if provider.api_key.as_deref().is_none_or(|key| key.trim().is_empty())
+                && !KEYLESS_PROVIDERS.contains(&provider.model_provider.as_str())
+            {
+                error(at, format!("{} needs an api key", provider.model_provider));
+            }
+            if let Some(base_url) = provider.base_url() {
+                match Url::parse(base_url) {
+                    Ok(url) if matches!(url.scheme(), "http" | "https") => {}
+                    Ok(_) => error(at, format!("base url \"{}\" is not http(s)", base_url)),
+                    Err(e) => error(at, format!("base url \"{}\": {}", base_url, e)),
+                }
+            }
+        }
+
+        errors
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn provider(model_provider: &str, api_key: Option<&str>) -> ProviderConfig {
+        ProviderConfig {
+            model_provider: model_provider.to_string(),
+            model: "some-model".to_string(),
+            api_key: api_key.map(str::to_string),
+            ..Default::default()
+        }
+    }
+
+    fn config(providers: Vec<(&str, ProviderConfig)>) -> ModelConfig {
+        ModelConfig {
+            active_provider: providers.first().map_or(FAKE_PROVIDER, |(name, _)| *name).to_string(),
+            configs: providers.into_iter().map(|(name, provider)| (name.to_string(), provider)).collect(),
+            ..Default::default()
+        }
+    }
+
+    fn messages(config: &ModelConfig) -> Vec<(Option<String>, String)> {
+        config.validate().into_iter().map(|error| (error.provider, error.message)).collect()
+    }
+
+    #[test]
+    fn valid_configs_have_no_errors() {
+        assert!(ModelConfig::default().validate().is_empty());
+        let mut openai = provider("openai", Some("sk-1"));
+        openai.set_base_url(Some("https://api.openai.com/v1".to_string()));
+        let config = config(vec![("main", openai), ("local", provider("ollama", None))]);
+        assert!(config.validate().is_empty());
+    }
+
+    #[test]
+    fn unknown_provider() {
+        let config = config(vec![("main", provider("openia", Some("sk-1")))]);
+        assert_eq!(messages(&config), vec![
+            (Some("main".to_string()), "unknown model provider \"openia\"".to_string()),
+        ]);
+    }
+
+    #[test]
+    fn missing_key_on_keyed_provider() {
+        for api_key in [None, Some(""), Some("  ")] {
+            let config = config(vec![("main", provider("anthropic", api_key))]);
+            assert_eq!(messages(&config), vec![
+                (Some("main".to_string()), "anthropic needs an api key".to_string()),
+            ]);
+        }
+    }
+
+    #[test]
+    fn base_url_must_be_http() {
+        for (base_url, message) in [
+            ("ftp://example.com", "base url \"ftp://example.com\" is not http(s)"),
+            ("localhost:11434", "base url \"localhost:11434\" is not http(s)"),
+            ("not a url", "base url \"not a url\": relative URL without a base"),
+        ] {
+            let mut ollama = provider("ollama", None);
+            ollama.set_base_url(Some(base_url.to_string()));
+            let config = config(vec![("local", ollama)]);
+            assert_eq!(messages(&config), vec![(Some("local".to_string()), message.to_string())]);
+        }
+    }
+
+    #[test]
+    fn active_provider_must_be_configured() {
+        let mut config = config(vec![("main", provider("openai", Some("sk-1")))]);
+        config.active_provider = "gone".to_string();
+        assert_eq!(messages(&config), vec![
+            (None, "active provider \"gone\" is not configured".to_string()),
+        ]);
+    }
+
+    #[test]
+    fn round_trip_keeps_unknown_settings() {
+        let json = serde_json::json!({
+            "activeProvider": "main",
+            "enableTools": false,
+            "disableDiveSystemPrompt": true,
+            "configs": {
+                "main": {
+                    "modelProvider": "openai",
+                    "model": "gpt-4o",
+                    "apiKey": "sk-1",
+                    "active": true,
+                    "configuration": {"baseURL": "https://api.openai.com/v1", "temperature": 0.5},
+                },
+            },
+            "customRules": "be brief",
+        });
+        let config: ModelConfig = serde_json::from_value(json.clone()).unwrap();
+        assert_eq!(config.extra["customRules"], "be brief");
+        assert_eq!(config.configs["main"].extra["active"], true);
+        assert_eq!(serde_json::to_value(&config).unwrap(), json);
+    }
+}
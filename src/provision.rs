@@ -0,0 +1,405 @@
+use std::{
+    collections::HashSet,
+    path::{Component, Path},
+    process::Stdio,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::Result;
+use tokio::process::Command;
+use tracing::info;
+
+use crate::host::{HostConfig, HOST_DB_FILE};
+
+/// Records which local directory or tarball the host was installed from.
+const SOURCE_MARKER: &str = ".tive-source";
+/// The environment `uv sync` keeps up to date, kept across local installs.
+const VENV_DIR: &str = ".venv";
+
+/// What is installed in the host directory, compared to what the config wants.
+#[derive(Debug)]
+pub struct Installed {
+    pub version: Option<String>,
+    pub current: bool,
+}
+
+/// Make sure `dir` holds the host version `config` asks for, touching the
+/// network only when it does not.
+pub async fn provision(config: &HostConfig, dir: &Path) -> Result<()> {
+    let changed = match &config.source {
+        Some(source) => install_local(source, dir).await?,
+        None => checkout(&config.repo, &config.tag, dir).await?,
+    };
+
+    let uses_uv = config.command.first().is_some_and(|program| program == "uv");
+    if uses_uv && (changed || !dir.join(VENV_DIR).exists()) {
+        info!("Sync host dependencies");
+        run(Command::new("uv").arg("sync").current_dir(dir), "uv sync").await?;
+    }
+
+    Ok(())
+}
+
+/// Inspect the host directory without changing it.
+pub async fn installed(config: &HostConfig, dir: &Path) -> Installed {
+    match &config.source {
+        Some(source) => {
+            let marker = tokio::fs::read_to_string(dir.join(SOURCE_MARKER)).await.ok();
+            let current = source_stamp(source).await.ok().is_some_and(|stamp| marker.as_deref() == Some(stamp.as_str()));
+            Installed { version: marker, current }
+        }
+        None => {
+            let head = rev_parse(dir, "HEAD").await;
+            let wanted = rev_parse(dir, &format!("{}^{{commit}}", config.tag)).await;
+            let version = match head {
+                Some(_) => git(dir, &["describe", "--tags", "--always"]).await.ok(),
+                None => None,
+            };
+            // a local install over the checkout replaced its files
+            let local = dir.join(SOURCE_MARKER).exists();
+            Installed { version, current: head.is_some() && head == wanted && !local }
+        }
+    }
+}
+
+/// Check out `rev` of `repo` in `dir`, fetching only if `rev` is unknown.
+/// Returns whether the checkout changed.
+async fn checkout(repo: &str, rev: &str, dir: &Path) -> Result<bool> {
+    tokio::fs::create_dir_all(dir).await?;
+
+    // files copied by a local install would block the checkout, or stay in
+    // place of the checked out ones
+    let marker = dir.join(SOURCE_MARKER);
+    let local = marker.exists();
+    if local {
+        info!("Remove the local host install");
+        clear_install(dir).await?;
+    }
+
+    // the directory may already hold the host database, so init in place
+    // rather than cloning into it
+    if !dir.join(".git").exists() {
+        info!("Initialize host checkout from {}", repo);
+        git(dir, &["init", "--quiet"]).await?;
+        git(dir, &["remote", "add", "origin", repo]).await?;
+    }
+
+    let wanted_rev = format!("{}^{{commit}}", rev);
+    let head = rev_parse(dir, "HEAD").await;
+    let mut wanted = rev_parse(dir, &wanted_rev).await;
+    if head.is_some() && head == wanted && !local {
+        return Ok(false);
+    }
+
+    if wanted.is_none() {
+        info!("Fetch host {} from {}", rev, repo);
+        git(dir, &["fetch", "--quiet", "--tags", "origin"]).await?;
+        wanted = rev_parse(dir, &wanted_rev).await;
+        if wanted.is_none() {
+            return Err(anyhow::anyhow!("host version {} not found in {}", rev, repo));
+        }
+    }
+
+    info!("Check out host {}", rev);
+    git(dir, &["checkout", "--quiet", "--force", "--detach", rev]).await?;
+    if local {
+        tokio::fs::remove_file(&marker).await?;
+    }
+    Ok(true)
+}
+
+/// Copy a host directory or unpack a host tarball into `dir`, in place of the
+/// earlier install, unless that exact source is already installed. Returns
+/// whether anything was copied.
+async fn install_local(source: &Path, dir: &Path) -> Result<bool> {
+    let stamp = source_stamp(source).await?;
+    let marker = dir.join(SOURCE_MARKER);
+    if tokio::fs::read_to_string(&marker).await.ok().as_deref() == Some(stamp.as_str()) {
+        return Ok(false);
+    }
+
+    tokio::fs::create_dir_all(dir).await?;
+    if source.canonicalize()?.starts_with(dir.canonicalize()?) {
+        return Err(anyhow::anyhow!(
+            "host source {} is inside the host directory {}",
+            source.display(),
+            dir.display(),
+        ));
+    }
+
+    // a copy failing halfway must not pass for the earlier install
+    if marker.exists() {
+        tokio::fs::remove_file(&marker).await?;
+    }
+    clear_install(dir).await?;
+    if source.is_dir() {
+        info!("Install host from {}", source.display());
+        let mut from = source.as_os_str().to_owned();
+        from.push("/.");
+        run(Command::new("cp").arg("-a").arg(from).arg(dir), "copy host").await?;
+    } else {
+        info!("Unpack host from {}", source.display());
+        let listing = run(Command::new("tar").arg("-tf").arg(source), "list host tarball").await?;
+        let strip = strip_components(&listing);
+        run(
+            Command::new("tar")
+                .arg("-xf")
+                .arg(source)
+                .arg("-C")
+                .arg(dir)
+                .arg(format!("--strip-components={}", strip)),
+            "unpack host tarball",
+        ).await?;
+    }
+
+    tokio::fs::write(&marker, stamp).await?;
+    Ok(true)
+}
+
+/// Remove what an earlier install left in `dir`, so files deleted from the
+/// source do not linger. The host database and environment stay, and so does
+/// the git checkout so going back to it does not fetch again. The source
+/// marker is left for the caller to replace once the new install is done.
+async fn clear_install(dir: &Path) -> Result<()> {
+    let mut entries = tokio::fs::read_dir(dir).await?;
+    while let Some(entry) = entries.next_entry().await? {
+        let name = entry.file_name();
+        let name = name.to_string_lossy();
+        if name.starts_with(HOST_DB_FILE) || [VENV_DIR, ".git", SOURCE_MARKER].contains(&name.as_ref()) {
+            continue;
+        }
+
+        if entry.file_type().await?.is_dir() {
+            tokio::fs::remove_dir_all(entry.path()).await?;
+        } else {
+            tokio::fs::remove_file(entry.path()).await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Identify a local source by where it lives and when anything in it last
+/// changed.
+async fn source_stamp(source: &Path) -> Result<String> {
+    let path = source.canonicalize()
+        .map_err(|e| anyhow::anyhow!("host source {}: {}", source.display(), e))?;
+    let walked = path.clone();
+    let modified = tokio::task::spawn_blocking(move || newest_modified(&walked)).await??;
+    let modified = modified.duration_since(UNIX_EPOCH)?.as_millis();
+    Ok(format!("{}@{}", path.display(), modified))
+}
+
+/// The latest modification time of `path` and everything below it, not
+/// following symlinks.
+fn newest_modified(path: &Path) -> Result<SystemTime> {
+    let metadata = path.symlink_metadata()?;
+    let mut newest = metadata.modified()?;
+    if metadata.is_dir() {
+        for entry in std::fs::read_dir(path)? {
+            newest = newest.max(newest_modified(&entry?.path())?);
+        }
+    }
+
+    Ok(newest)
+}
+
+/// How many leading components tar has to strip to drop the one top-level
+/// directory every entry sits below, as in release archives, 0 if there is
+/// none. tar counts a leading `.` as a component of its own.
+fn strip_components(listing: &str) -> usize {
+    let mut roots = HashSet::new();
+    let mut nested = false;
+    for entry in listing.lines() {
+        let mut components = Path::new(entry).components().peekable();
+        let mut dots = 0;
+        while components.next_if_eq(&Component::CurDir).is_some() {
+            dots += 1;
+        }
+        let Some(root) = components.next() else {
+            continue;
+        };
+        nested |= components.next().is_some();
+        roots.insert((dots, root));
+    }
+
+    match roots.into_iter().collect::<Vec<_>>()[..] {
+        [(dots, Component::Normal(_))] if nested => dots + 1,
+        _ => 0,
+    }
+}
+
+async fn rev_parse(dir: &Path, rev: &str) -> Option<String> {
+    git(dir, &["rev-parse", "--verify", "--quiet", rev]).await.ok()
+}
+
+async fn git(dir: &Path, args: &[&str]) -> Result<String> {
+    let what = format!("git {}", args.first().unwrap_or(&""));
+    run(Command::new("git").args(args).current_dir(dir), &what).await
+}
+
+/// Run a command to completion, failing with its stderr if it does not succeed.
+async fn run(cmd: &mut Command, what: &str) -> Result<String> {
+    let output = cmd
+        .stdin(Stdio::null())
+        .env("PATH", std::env::var("PATH").unwrap_or_default())
+        .output()
+        .await
+        .map_err(|e| anyhow::anyhow!("{}: {}", what, e))?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(anyhow::anyhow!("{} failed ({}): {}", what, output.status, stderr.trim()));
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::PathBuf, time::Duration};
+
+    use super::*;
+
+    /// An empty directory of its own under the system temp directory.
+    fn scratch(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("tive-provision-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn touch_later(path: &Path, seconds: u64) {
+        let file = fs::File::options().write(true).open(path).unwrap();
+        file.set_modified(SystemTime::now() + Duration::from_secs(seconds)).unwrap();
+    }
+
+    fn run_sync(program: &str, args: &[&str], dir: &Path) {
+        let status = std::process::Command::new(program).args(args).current_dir(dir).status().unwrap();
+        assert!(status.success(), "{} {:?} failed", program, args);
+    }
+
+    #[test]
+    fn strip_root_of_release_archive() {
+        assert_eq!(strip_components("host-1.0/\nhost-1.0/pyproject.toml\nhost-1.0/src/main.py\n"), 1);
+        assert_eq!(strip_components("./\n./host/\n./host/pyproject.toml\n"), 2);
+    }
+
+    #[test]
+    fn no_strip_for_flat_archive() {
+        assert_eq!(strip_components("pyproject.toml\nsrc/\nsrc/main.py\n"), 0);
+        assert_eq!(strip_components("./\n./pyproject.toml\n./src/main.py\n"), 0);
+        assert_eq!(strip_components("a/x\nb/y\n"), 0);
+        // one file is not a directory to strip
+        assert_eq!(strip_components("pyproject.toml\n"), 0);
+        assert_eq!(strip_components("./pyproject.toml\n"), 0);
+        assert_eq!(strip_components(""), 0);
+    }
+
+    #[tokio::test]
+    async fn install_unpacks_dotted_tarball() {
+        let root = scratch("tarball");
+        fs::create_dir_all(root.join("staging/host/src")).unwrap();
+        fs::write(root.join("staging/host/pyproject.toml"), "[project]").unwrap();
+        fs::write(root.join("staging/host/src/main.py"), "print()").unwrap();
+        run_sync("tar", &["-cf", "../host.tar", "./host"], &root.join("staging"));
+
+        let dir = root.join("dir");
+        assert!(install_local(&root.join("host.tar"), &dir).await.unwrap());
+        assert!(dir.join("pyproject.toml").exists());
+        assert!(dir.join("src/main.py").exists());
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[tokio::test]
+    async fn install_refuses_source_in_host_dir() {
+        let dir = scratch("inside");
+        fs::create_dir_all(dir.join("src")).unwrap();
+        fs::write(dir.join("src/main.py"), "print()").unwrap();
+
+        assert!(install_local(&dir, &dir).await.is_err());
+        assert!(install_local(&dir.join("src"), &dir).await.is_err());
+        assert!(dir.join("src/main.py").exists());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn stamp_follows_nested_changes() {
+        let dir = scratch("stamp");
+        fs::create_dir_all(dir.join("src/pkg")).unwrap();
+        fs::write(dir.join("src/pkg/main.py"), "print()").unwrap();
+
+        let before = source_stamp(&dir).await.unwrap();
+        assert_eq!(source_stamp(&dir).await.unwrap(), before);
+
+        touch_later(&dir.join("src/pkg/main.py"), 60);
+        let after = source_stamp(&dir).await.unwrap();
+        assert_ne!(after, before);
+        assert!(after.starts_with(&format!("{}@", dir.canonicalize().unwrap().display())));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn stamp_of_missing_source_fails() {
+        let dir = scratch("missing");
+        assert!(source_stamp(&dir.join("nothing")).await.is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn install_replaces_earlier_files() {
+        let root = scratch("install");
+        let (source, dir) = (root.join("source"), root.join("host"));
+        fs::create_dir_all(source.join("src")).unwrap();
+        fs::write(source.join("src/new.py"), "new").unwrap();
+        fs::create_dir_all(dir.join("src")).unwrap();
+        fs::create_dir_all(dir.join(VENV_DIR)).unwrap();
+        fs::write(dir.join("src/deleted.py"), "old").unwrap();
+        fs::write(dir.join(HOST_DB_FILE), "db").unwrap();
+        fs::write(dir.join(format!("{}-wal", HOST_DB_FILE)), "wal").unwrap();
+
+        assert!(install_local(&source, &dir).await.unwrap());
+        assert!(dir.join("src/new.py").exists());
+        assert!(!dir.join("src/deleted.py").exists());
+        assert!(dir.join(HOST_DB_FILE).exists());
+        assert!(dir.join(format!("{}-wal", HOST_DB_FILE)).exists());
+        assert!(dir.join(VENV_DIR).exists());
+
+        // the same source is not copied twice
+        assert!(!install_local(&source, &dir).await.unwrap());
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[tokio::test]
+    async fn checkout_replaces_local_install() {
+        let root = scratch("checkout");
+        let (repo, source, dir) = (root.join("repo"), root.join("source"), root.join("host"));
+        fs::create_dir_all(&repo).unwrap();
+        fs::write(repo.join("pyproject.toml"), "[project]").unwrap();
+        run_sync("git", &["init", "--quiet"], &repo);
+        run_sync("git", &["add", "."], &repo);
+        let identity = ["-c", "user.name=t", "-c", "user.email=t@t"];
+        run_sync("git", &[&identity[..], &["commit", "--quiet", "-m", "init"]].concat(), &repo);
+        run_sync("git", &["tag", "v1"], &repo);
+        fs::create_dir_all(&source).unwrap();
+        fs::write(source.join("pyproject.toml"), "[local]").unwrap();
+        fs::write(source.join("local.py"), "print()").unwrap();
+
+        let repo = repo.to_str().unwrap();
+        assert!(checkout(repo, "v1", &dir).await.unwrap());
+        assert!(install_local(&source, &dir).await.unwrap());
+        assert!(dir.join(".git").exists());
+
+        assert!(checkout(repo, "v1", &dir).await.unwrap());
+        assert_eq!(fs::read_to_string(dir.join("pyproject.toml")).unwrap(), "[project]");
+        assert!(!dir.join("local.py").exists());
+        assert!(!dir.join(SOURCE_MARKER).exists());
+        assert!(!checkout(repo, "v1", &dir).await.unwrap());
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
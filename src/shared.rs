@@ -1,13 +1,24 @@
 use std::{
+    collections::BTreeMap,
     path::PathBuf,
-    sync::{Arc, LazyLock},
+    sync::{LazyLock, OnceLock},
 };
 
 use directories::{ProjectDirs, UserDirs};
 
+use crate::{
+    chat::ThreadKey,
+    client::ChatInfo,
+    host::HostMessage,
+    mcp_config::{McpConfig, McpServerStatus},
+    model_config::{ModelConfig, ProviderConfig},
+    supervisor::HostHealth,
+};
+
 pub static PROJECT_NAME: LazyLock<String> = LazyLock::new(|| env!("CARGO_CRATE_NAME").to_uppercase().to_string());
+static CONFIG_DIR: OnceLock<PathBuf> = OnceLock::new();
 pub static PROJECT_DIRS: LazyLock<Dirs> = LazyLock::new(|| {
-    ProjectDirs::from("", "", "tive")
+    let mut dirs = ProjectDirs::from("", "", "tive")
         .map(|proj_dirs| Dirs {
             config: proj_dirs.config_dir().to_path_buf(),
             data: proj_dirs.data_local_dir().to_path_buf(),
@@ -30,9 +41,21 @@ pub static PROJECT_DIRS: LazyLock<Dirs> = LazyLock::new(|| {
                 data: PathBuf::from("./.tive/data"),
                 cache: PathBuf::from("./.tive/cache"),
             }
-        })
+        });
+
+    if let Some(config) = CONFIG_DIR.get() {
+        dirs.config = config.clone();
+    }
+
+    dirs
 });
 
+/// Use `dir` instead of the platform config directory, must be called before
+/// `PROJECT_DIRS` is first touched.
+pub fn set_config_dir(dir: PathBuf) {
+    let _ = CONFIG_DIR.set(dir);
+}
+
 #[derive(Debug, Clone)]
 pub struct Dirs {
     pub config: PathBuf,
@@ -52,20 +75,95 @@ impl Dirs {
     pub fn host_cache_dir(&self) -> PathBuf {
         self.cache.join("host")
     }
+
+    /// The full-text index of every chat on the host.
+    pub fn chat_index_dir(&self) -> PathBuf {
+        self.cache.join("index")
+    }
+
+    /// Output of the host process, rotated next to it. Kept out of
+    /// `host_data_dir`, which is replaced when the host is provisioned.
+    pub fn host_log_file(&self) -> PathBuf {
+        self.data.join("logs").join("dive_httpd.log")
+    }
 }
 
 pub enum UIAction {
     Quit,
     Chat {
-        id: Option<Arc<String>>,
+        key: ThreadKey,
         message: String,
     },
+    /// Generate the answer with the ai message `message_id` again.
+    Retry {
+        key: ThreadKey,
+        message_id: String,
+    },
+    /// Replace the user message `message_id` by `message` and answer it.
+    Edit {
+        key: ThreadKey,
+        message_id: String,
+        message: String,
+    },
+    Abort,
+    ListChats,
+    LoadChat {
+        key: ThreadKey,
+        id: String,
+    },
+    LoadModelConfig,
+    /// Push `ModelConfig` to the host.
+    SaveModelConfig(ModelConfig),
+    /// Ask each configured provider which models it offers.
+    ListModels(BTreeMap<String, ProviderConfig>),
+    /// Read the mcp config and the status of every server.
+    LoadMcp,
+    /// Push `McpConfig` to the host, then read the servers again.
+    SaveMcpConfig(McpConfig),
 }
 
 pub enum UIActionResult {
     End,
+    Host(HostHealth),
+    /// A host process was (re)started.
+    HostStarted {
+        pid: Option<u32>,
+    },
+    HostBus(HostMessage),
+    ChatList(Vec<ChatInfo>),
+    /// The chat asked for is in the thread, or why it is not.
+    ChatLoaded {
+        key: ThreadKey,
+        loaded: Result<(), String>,
+    },
+    ModelConfig(Result<ModelConfig, String>),
+    ModelConfigSaved(Result<(), String>),
+    /// The model chats are now answered by.
+    ActiveModel(String),
+    /// The models a configured provider offers.
+    ProviderModels {
+        provider: String,
+        models: Vec<String>,
+    },
+    McpConfig(Result<McpConfig, String>),
+    McpSaved(Result<(), String>),
+    McpServers(Result<Vec<McpServerStatus>, String>),
     Chat {
-        id: Arc<String>,
+        key: ThreadKey,
         content: String,
     },
-}
\ No newline at end of file
+    ToolCall {
+        key: ThreadKey,
+        name: String,
+        arguments: String,
+    },
+    ToolResult {
+        key: ThreadKey,
+        name: String,
+        result: String,
+    },
+    Error {
+        key: ThreadKey,
+        message: String,
+    },
+}
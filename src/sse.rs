@@ -0,0 +1,243 @@
+//! Incremental decoder for `text/event-stream` bodies, following the html
+//! living standard's interpretation of server-sent events.
+
+/// One dispatched server-sent event.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SseEvent {
+    /// The `event:` type, `None` for the default `message`.
+    pub event: Option<String>,
+    /// Every `data:` line of the event, joined by `\n`.
+    pub data: String,
+    /// The last event id seen on the stream so far.
+    pub id: Option<String>,
+}
+
+/// Turns arbitrarily split chunks of an event stream into events.
+#[derive(Debug, Default)]
+pub struct SseDecoder {
+    /// Bytes of the current, unterminated line.
+    line: Vec<u8>,
+    /// The previous chunk ended in `\r`, so a leading `\n` belongs to it.
+    after_cr: bool,
+    started: bool,
+    event: Option<String>,
+    data: String,
+    has_data: bool,
+    id: Option<String>,
+}
+
+impl SseDecoder {
+    /// Feed the next chunk, returning the events it completes.
+    pub fn push(&mut self, mut chunk: &[u8]) -> Vec<SseEvent> {
+        let mut events = vec![];
+        if chunk.is_empty() {
+            return events;
+        }
+
+        if self.after_cr && chunk.first() == Some(&b'\n') {
+            chunk = &chunk[1..];
+        }
+        self.after_cr = false;
+
+        while let Some(end) = chunk.iter().position(|b| *b == b'\n' || *b == b'\r') {
+            self.line.extend_from_slice(&chunk[..end]);
+            let line = std::mem::take(&mut self.line);
+            if let Some(event) = self.process_line(&line) {
+                events.push(event);
+            }
+
+            let crlf = chunk[end] == b'\r' && chunk.get(end + 1) == Some(&b'\n');
+            if chunk[end] == b'\r' && end + 1 == chunk.len() {
+                self.after_cr = true;
+            }
+            chunk = &chunk[end + if crlf { 2 } else { 1 }..];
+        }
+        self.line.extend_from_slice(chunk);
+
+        events
+    }
+
+    fn process_line(&mut self, line: &[u8]) -> Option<SseEvent> {
+        let mut line = line;
+        if !self.started {
+            self.started = true;
+            line = line.strip_prefix("\u{feff}".as_bytes()).unwrap_or(line);
+        }
+
+        if line.is_empty() {
+            return self.dispatch();
+        }
+        if line.starts_with(b":") {
+            return None;
+        }
+
+        let (field, value) = match line.iter().position(|b| *b == b':') {
+            Some(colon) => {
+                let value = &line[colon + 1..];
+                (&line[..colon], value.strip_prefix(b" ").unwrap_or(value))
+            }
+            None => (line, &b""[..]),
+        };
+        let value = String::from_utf8_lossy(value);
+
+        match field {
+            b"event" => self.event = Some(value.into_owned()),
+            b"data" => {
+                if self.has_data {
+                    self.data.push('\n');
+                }
+                self.data.push_str(&value);
+                self.has_data = true;
+            }
+            b"id" if !value.contains('\0') => self.id = Some(value.into_owned()),
+            // reconnection is up to the caller, and unknown fields are ignored
+            _ => {}
+        }
+
+        None
+    }
+
+    fn dispatch(&mut self) -> Option<SseEvent> {
+        let event = self.event.take();
+        if !std::mem::take(&mut self.has_data) {
+            return None;
+        }
+
+        Some(SseEvent {
+            event: event.filter(|e| !e.is_empty()),
+            data: std::mem::take(&mut self.data),
+            id: self.id.clone(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decode_chunks(chunks: &[&[u8]]) -> Vec<SseEvent> {
+        let mut decoder = SseDecoder::default();
+        chunks.iter().flat_map(|chunk| decoder.push(chunk)).collect()
+    }
+
+    fn decode(body: &str) -> Vec<SseEvent> {
+        decode_chunks(&[body.as_bytes()])
+    }
+
+    fn data(data: &str) -> SseEvent {
+        SseEvent { data: data.to_string(), ..Default::default() }
+    }
+
+    /// A tiny deterministic generator, so fuzzing needs no extra crates.
+    struct Lcg(u64);
+
+    impl Lcg {
+        fn next(&mut self, bound: usize) -> usize {
+            self.0 = self.0.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            ((self.0 >> 33) as usize) % bound
+        }
+    }
+
+    const STREAM: &str = "\u{feff}: keep-alive\r\n\
+        data: {\"message\": \"{\\\"type\\\": \\\"text\\\", \\\"content\\\": \\\"héllo 世界\\\"}\"}\r\n\r\n\
+        event: ping\nid: 7\ndata\n\n\
+        data: first\rdata:second\r\r\
+        retry: 10\nunknown: field\n\
+        data:  two spaces\n\n\
+        event: ignored without data\n\n\
+        data: [DONE]\n\n";
+
+    #[test]
+    fn decodes_single_event() {
+        assert_eq!(decode("data: hello\n\n"), vec![data("hello")]);
+    }
+
+    #[test]
+    fn keeps_incomplete_event_pending() {
+        let mut decoder = SseDecoder::default();
+        assert!(decoder.push(b"data: hello\n").is_empty());
+        assert!(decoder.push(b"data: again").is_empty());
+        assert_eq!(decoder.push(b"\n\n"), vec![data("hello\nagain")]);
+    }
+
+    #[test]
+    fn splits_coalesced_events() {
+        assert_eq!(decode("data: a\n\ndata: b\n\n"), vec![data("a"), data("b")]);
+    }
+
+    #[test]
+    fn joins_multi_line_data() {
+        assert_eq!(decode("data: a\ndata: b\ndata\n\n"), vec![data("a\nb\n")]);
+    }
+
+    #[test]
+    fn reads_event_and_id_and_skips_comments() {
+        let events = decode(": comment\nevent: update\nid: 3\ndata: x\n\ndata: y\n\n");
+        assert_eq!(events, vec![
+            SseEvent { event: Some("update".into()), data: "x".into(), id: Some("3".into()) },
+            SseEvent { event: None, data: "y".into(), id: Some("3".into()) },
+        ]);
+    }
+
+    #[test]
+    fn handles_crlf_and_cr() {
+        assert_eq!(decode("data: a\r\n\r\ndata: b\r\rdata: c\n\n"), vec![data("a"), data("b"), data("c")]);
+    }
+
+    #[test]
+    fn strips_only_one_leading_space() {
+        assert_eq!(decode("data:a\n\ndata:  b\n\n"), vec![data("a"), data(" b")]);
+    }
+
+    #[test]
+    fn skips_events_without_data() {
+        assert_eq!(decode("event: x\n\n\n\nid: 1\n\n"), vec![]);
+    }
+
+    #[test]
+    fn crlf_split_across_chunks_is_one_line_end() {
+        let events = decode_chunks(&[b"data: a\r", b"\n", b"\r", b"\ndata: b\n\n"]);
+        assert_eq!(events, vec![data("a"), data("b")]);
+    }
+
+    #[test]
+    fn same_events_for_every_split() {
+        let body = STREAM.as_bytes();
+        let expected = decode(STREAM);
+        assert_eq!(expected.len(), 5);
+        assert!(expected[0].data.contains("héllo 世界"));
+        assert_eq!(expected[1], SseEvent { event: Some("ping".into()), data: String::new(), id: Some("7".into()) });
+        assert_eq!(expected[2].data, "first\nsecond");
+        assert_eq!(expected[3].data, " two spaces");
+        assert_eq!(expected[4].data, "[DONE]");
+
+        for at in 0..=body.len() {
+            assert_eq!(decode_chunks(&[&body[..at], &body[at..]]), expected, "split at {}", at);
+        }
+    }
+
+    #[test]
+    fn same_events_for_random_chunking() {
+        let body = STREAM.as_bytes();
+        let expected = decode(STREAM);
+        let mut rng = Lcg(42);
+
+        for round in 0..2000 {
+            let mut chunks = vec![];
+            let mut rest = body;
+            while !rest.is_empty() {
+                let len = rng.next(rest.len().min(12)) + 1;
+                let (chunk, tail) = rest.split_at(len);
+                chunks.push(chunk);
+                rest = tail;
+            }
+            assert_eq!(decode_chunks(&chunks), expected, "round {}", round);
+        }
+    }
+
+    #[test]
+    fn same_events_byte_by_byte() {
+        let chunks: Vec<&[u8]> = STREAM.as_bytes().chunks(1).collect();
+        assert_eq!(decode_chunks(&chunks), decode(STREAM));
+    }
+}
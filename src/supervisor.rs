@@ -0,0 +1,136 @@
+use std::time::Duration;
+
+use anyhow::Result;
+use tokio::{
+    sync::mpsc::{Receiver, Sender},
+    task::JoinHandle,
+    time::timeout,
+};
+use tracing::{error, info, warn};
+
+use crate::{
+    client::ChatClient,
+    host::{self, HostEvent, HostMessage, HostProcess},
+    shared::UIActionResult,
+};
+
+const BACKOFF_MIN: Duration = Duration::from_secs(1);
+const BACKOFF_MAX: Duration = Duration::from_secs(30);
+/// How long a restarted host may take to report its address.
+const LISTEN_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// What the ui knows about the host.
+#[derive(Debug, Clone, Default)]
+pub enum HostHealth {
+    #[default]
+    Connecting,
+    Up {
+        endpoint: String,
+        /// An error the host reported on the status bus while still running.
+        error: Option<String>,
+    },
+    Restarting {
+        attempt: u32,
+        error: String,
+    },
+}
+
+/// Watch a spawned host, forwarding its status bus to the ui, and restart it
+/// with backoff when it exits, pointing `client` at the new address once it
+/// is back.
+pub fn supervise(
+    mut process: HostProcess,
+    mut events: Receiver<HostEvent>,
+    listen_message: Option<HostMessage>,
+    client: ChatClient,
+    tx: Sender<UIActionResult>,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let _ = tx.send(UIActionResult::HostStarted { pid: process.pid() }).await;
+        if let Some(message) = listen_message {
+            let _ = tx.send(UIActionResult::HostBus(message)).await;
+        }
+
+        loop {
+            let mut error = tokio::select! {
+                status = process.wait() => match status {
+                    Ok(status) => format!("host exited: {}", status),
+                    Err(e) => format!("host lost: {}", e),
+                },
+                Some(evt) = events.recv() => {
+                    let error = match evt {
+                        HostEvent::BusMessage(message) => {
+                            info!("Host state: {}", message.status.state);
+                            let error = reported_error(&message);
+                            let _ = tx.send(UIActionResult::HostBus(message)).await;
+                            error
+                        }
+                        HostEvent::Error(e) => Some(e),
+                    };
+
+                    if let Some(error) = &error {
+                        warn!("Host reported: {}", error);
+                    }
+                    let _ = tx.send(UIActionResult::Host(HostHealth::Up {
+                        endpoint: client.endpoint(),
+                        error,
+                    })).await;
+                    continue;
+                },
+            };
+
+            error!("{}", error);
+            for attempt in 1.. {
+                let _ = tx.send(UIActionResult::Host(HostHealth::Restarting {
+                    attempt,
+                    error: error.clone(),
+                })).await;
+                tokio::time::sleep(backoff(attempt)).await;
+
+                match restart(&mut process, &mut events, &client).await {
+                    Ok(message) => {
+                        info!("Host restarted at {}", client.endpoint());
+                        let _ = tx.send(UIActionResult::HostStarted { pid: process.pid() }).await;
+                        let _ = tx.send(UIActionResult::HostBus(message)).await;
+                        let _ = tx.send(UIActionResult::Host(HostHealth::Up {
+                            endpoint: client.endpoint(),
+                            error: None,
+                        })).await;
+                        break;
+                    }
+                    Err(e) => {
+                        error!("Failed to restart host: {}", e);
+                        error = e.to_string();
+                    }
+                }
+            }
+        }
+    })
+}
+
+async fn restart(process: &mut HostProcess, events: &mut Receiver<HostEvent>, client: &ChatClient) -> Result<HostMessage> {
+    // drop whatever the old process left on the bus
+    while events.try_recv().is_ok() {}
+
+    process.spawn().await?;
+    let message = timeout(LISTEN_TIMEOUT, host::wait_for_listen(events)).await
+        .map_err(|_| anyhow::anyhow!("host did not report its address"))??;
+    let (ip, port) = message.listen().unwrap_or_default();
+    client.set_local(ip, port)?;
+    client.wait_for_server().await?;
+    Ok(message)
+}
+
+/// The error a bus message carries, with its code if there is one.
+fn reported_error(message: &HostMessage) -> Option<String> {
+    let status = &message.status;
+    let error = status.last_error.as_deref()?;
+    Some(match &status.error_code {
+        Some(code) => format!("{} ({})", error, code),
+        None => error.to_string(),
+    })
+}
+
+fn backoff(attempt: u32) -> Duration {
+    BACKOFF_MIN.saturating_mul(1 << attempt.saturating_sub(1).min(5)).min(BACKOFF_MAX)
+}
@@ -1,17 +1,17 @@
-use std::{io::stdout, sync::Arc, time::Duration};
+use std::{collections::{HashMap, VecDeque}, io::stdout, sync::Arc, time::{Duration, Instant}};
 
 use anyhow::Result;
 use tracing::{error, info};
 use crossterm::{
-    event::{EventStream, KeyEvent, KeyModifiers},
+    event::{DisableMouseCapture, EnableMouseCapture, EventStream, KeyEvent, KeyModifiers, MouseButton, MouseEvent, MouseEventKind},
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
     ExecutableCommand,
 };
 use ratatui::{
     crossterm::event::{ Event, KeyCode },
-    layout::Rect,
+    layout::{Position, Rect},
     style::{Color, Style},
-    widgets::{Block, BorderType, Borders}, DefaultTerminal,
+    widgets::{Block, BorderType, Borders, ListState}, DefaultTerminal,
 };
 use futures_util::{FutureExt, StreamExt};
 use ratatui::{
@@ -19,16 +19,26 @@ use ratatui::{
     Frame,
 };
 use tokio::{process::Command, sync::mpsc::{Receiver, Sender}};
-use tui_textarea::TextArea;
+use tui_textarea::{CursorMove, TextArea};
 
 use crate::{
-    chat::ChatReader,
-    host::{MCP_CONFIG_FILE, MODEL_CONFIG_FILE},
-    message::{AIMessage, MessageFrame, UserMessage},
+    chat::{ChatReader, ChatSummary, ThreadKey},
+    clipboard,
+    client::ChatInfo,
+    host::{HostMessage, MCP_CONFIG_FILE, MODEL_CONFIG_FILE},
+    index::ChatIndex,
+    message::MessageFrame,
     shared::{UIAction, UIActionResult, PROJECT_DIRS},
-    widget::{message::{Message, MessageState}, status_bar::StatusBar},
+    supervisor::HostHealth,
+    widget::{chat_search::{ChatSearch, ChatSearchState}, diagnostics::Diagnostics, history::History, logs::{Logs, LogsState}, mcp::{Mcp, McpState}, model_form::{FormStatus, ModelForm, ModelFormState}, model_switcher::{ModelSwitcher, ModelSwitcherState}, message::{code_blocks, Message, MessageState, TranscriptView, Turn}, search::SearchOptions, sidebar::Sidebar, status_bar::StatusBar},
 };
 
+const SIDEBAR_WIDTH: u16 = 28;
+/// Status bus messages kept for the diagnostics panel.
+const BUS_HISTORY: usize = 50;
+/// Lines scrolled by one notch of the mouse wheel.
+const WHEEL_LINES: usize = 3;
+
 #[derive(Debug, Default, Clone, Copy)]
 pub enum InputMode {
     #[default]
@@ -36,12 +46,28 @@ pub enum InputMode {
     Insert,
     Leader,
     EditFile,
+    History,
+    Diagnostics,
+    Logs,
+    Search,
+    ChatSearch,
+    ModelConfig,
+    ModelSwitcher,
+    Mcp,
 }
 
 #[derive(Debug, Clone)]
 enum TuiInnerAction {
     OpenEditor(String),
     ForceRender,
+    NewChat,
+    OpenChat(String),
+    YankCode(usize),
+    Retry,
+    /// Load the user message of the current turn into the input.
+    EditTurn,
+    /// Show an earlier or later generation of the current turn.
+    Generation(isize),
 }
 
 #[derive(Debug)]
@@ -49,21 +75,53 @@ pub struct Tui<'a> {
     mode: InputMode,
     quit: bool,
     input: TextArea<'a>,
+    /// The user message the input replaces, instead of starting a new turn.
+    editing: Option<String>,
     tx: Sender<UIAction>,
     rx: Receiver<UIActionResult>,
     inner_tx: Sender<TuiInnerAction>,
     inner_rx: Receiver<TuiInnerAction>,
-    user_message: UserMessage,
-    ai_message: AIMessage,
+    frame: MessageFrame,
     streaming: bool,
+    streaming_key: Option<ThreadKey>,
     message_state: Option<MessageState>,
-    ct_index: usize,
-    thread_len: usize,
-    chat_id: Option<Arc<String>>,
+    /// The turn and its earlier generation on screen instead of the latest.
+    generation: Option<(usize, usize)>,
+    active: ThreadKey,
+    /// Scroll position of the conversations not on screen.
+    views: HashMap<ThreadKey, TranscriptView>,
+    summaries: Vec<ChatSummary>,
+    sidebar: bool,
+    count: Option<usize>,
+    /// `g` was pressed, waiting for the second one of `gg`.
+    pending_g: bool,
+    search_query: String,
+    search_options: SearchOptions,
+    /// The query is not a valid regular expression.
+    search_invalid: bool,
+    chats: Option<Vec<ChatInfo>>,
+    history_state: ListState,
+    index: Option<ChatIndex>,
+    chat_search: ChatSearchState,
+    model_form: ModelFormState,
+    model_switcher: ModelSwitcherState,
+    mcp: McpState,
+    /// The model answering the next message, once the host told it.
+    active_model: Option<String>,
+    /// A failure for the status bar, cleared by the next key.
+    notice: Option<String>,
+    /// Where the transcript and the input were last drawn, to place clicks.
+    chat_area: Rect,
+    input_area: Rect,
+    host: HostHealth,
+    host_pid: Option<u32>,
+    host_started: Option<Instant>,
+    host_messages: VecDeque<HostMessage>,
+    logs: LogsState,
 }
 
 impl<'a> Tui<'a> {
-    pub fn new(tx: Sender<UIAction>, rx: Receiver<UIActionResult>) -> Self {
+    pub fn new(tx: Sender<UIAction>, rx: Receiver<UIActionResult>, index: Option<ChatIndex>) -> Self {
         let (inner_tx, inner_rx) = tokio::sync::mpsc::channel(1);
 
         Self {
@@ -74,23 +132,51 @@ impl<'a> Tui<'a> {
             quit: false,
             mode: InputMode::default(),
             input: TextArea::default(),
-            user_message: UserMessage::default(),
-            ai_message: AIMessage::default(),
+            editing: None,
+            frame: MessageFrame::default(),
             streaming: false,
+            streaming_key: None,
             message_state: None,
-            ct_index: 0,
-            thread_len: 0,
-            chat_id: None,
+            generation: None,
+            active: 0,
+            views: HashMap::new(),
+            summaries: vec![],
+            sidebar: false,
+            count: None,
+            pending_g: false,
+            search_query: String::new(),
+            search_options: SearchOptions::default(),
+            search_invalid: false,
+            chats: None,
+            history_state: ListState::default(),
+            index,
+            chat_search: ChatSearchState::default(),
+            model_form: ModelFormState::default(),
+            model_switcher: ModelSwitcherState::default(),
+            mcp: McpState::default(),
+            active_model: None,
+            notice: None,
+            chat_area: Rect::default(),
+            input_area: Rect::default(),
+            host: HostHealth::default(),
+            host_pid: None,
+            host_started: None,
+            host_messages: VecDeque::with_capacity(BUS_HISTORY),
+            logs: LogsState::default(),
         }
     }
 
     pub async fn run(mut self, mut cr: ChatReader) {
         let mut terminal = ratatui::init();
+        if let Err(e) = stdout().execute(EnableMouseCapture) {
+            error!("failed to capture the mouse: {}", e);
+        }
         let mut reader = EventStream::new();
 
         let frame = terminal.get_frame();
-        let [chat_viewport, _, _] = layout(frame.area());
+        let [_, chat_viewport, _, _] = layout(frame.area(), self.sidebar);
         self.message_state = Some(MessageState::new(chat_viewport));
+        self.active = cr.registry().create().await;
 
         // 60 fps
         let mut animation_timer = tokio::time::interval(Duration::from_micros((1000. / 60. * 1000.) as u64));
@@ -103,7 +189,9 @@ impl<'a> Tui<'a> {
                 break;
             }
 
-            let animation = self.streaming;
+            // the diagnostics panel shows a running uptime, the log viewer
+            // follows its file
+            let animation = self.streaming || matches!(self.mode, InputMode::Diagnostics | InputMode::Logs);
             let clean_frame = !animation && last_animation_frame;
             let mut anima_tick = false;
 
@@ -111,8 +199,8 @@ impl<'a> Tui<'a> {
             self.tick_input_state();
 
             // get current chat to render to viewport
-            let ct = cr.read().await;
-            self.thread_len = ct.len();
+            self.summaries = cr.registry().summaries().await;
+            let ct = cr.read(self.active).await;
 
             if (animation && tick_by_animation) || (!animation && !tick_by_animation) || clean_frame {
                 terminal.draw(|f| draw(f, &mut self, ct)).expect("failed to draw frame");
@@ -125,22 +213,95 @@ impl<'a> Tui<'a> {
                         Ok(evt) => {
                             self.handle_input_event(evt).await;
                         },
-                        _ => (),
+                        Err(e) => error!("failed to read terminal event: {}", e),
                     }
                 },
                 _ = animation_timer.tick() => {
                     anima_tick = true;
+                    if let InputMode::Logs = self.mode {
+                        self.logs.poll();
+                    }
                 },
                 Some(evt) = self.rx.recv() => {
                     use UIActionResult::*;
                     match evt {
-                        Chat { content, id } => {
-                            self.ai_message.body.content.push_str(&content);
-                            self.chat_id = Some(id);
+                        Chat { content, key } if Some(key) == self.streaming_key => {
+                            self.frame.ai.body.content.push_str(&content);
+                        },
+                        ToolCall { name, arguments, key } if Some(key) == self.streaming_key => {
+                            self.frame.push_tool_call(name, arguments);
+                        },
+                        ToolResult { name, result, key } if Some(key) == self.streaming_key => {
+                            self.frame.set_tool_result(&name, result);
+                        },
+                        Error { message, key } if Some(key) == self.streaming_key => {
+                            self.frame.error = Some(message);
+                        },
+                        Chat { .. } | ToolCall { .. } | ToolResult { .. } | Error { .. } => {
+                            info!("drop stream event of an inactive chat");
+                        },
+                        Host(health) => {
+                            self.host = health;
+                        },
+                        HostStarted { pid } => {
+                            self.host_pid = pid;
+                            self.host_started = Some(Instant::now());
+                        },
+                        HostBus(message) => {
+                            if self.host_messages.len() == BUS_HISTORY {
+                                self.host_messages.pop_front();
+                            }
+                            self.host_messages.push_back(message);
+                        },
+                        ChatList(chats) => {
+                            self.history_state.select(if chats.is_empty() { None } else { Some(0) });
+                            self.chats = Some(chats);
+                        },
+                        ChatLoaded { key, loaded: Err(error) } => {
+                            self.notice = Some(format!("failed to load chat: {}", error));
+                            info!("chat {} not loaded", key);
+                        },
+                        ChatLoaded { key, loaded: Ok(()) } => {
+                            info!("chat loaded: {}", key);
+                            if key == self.active {
+                                self.generation = None;
+                                self.message_state.as_mut().unwrap().reset();
+                            } else {
+                                self.views.remove(&key);
+                            }
+                        },
+                        ModelConfig(Ok(config)) => match self.mode {
+                            InputMode::ModelSwitcher => {
+                                let configs = config.configs.clone();
+                                self.model_switcher.load(config);
+
+                                let tx = self.tx.clone();
+                                tokio::spawn(async move {
+                                    let _ = tx.send(UIAction::ListModels(configs)).await;
+                                });
+                            }
+                            _ => self.model_form.load(config),
+                        },
+                        ModelConfig(Err(error)) | ModelConfigSaved(Err(error)) => match self.mode {
+                            InputMode::ModelSwitcher => self.model_switcher.status = FormStatus::Failed(error),
+                            _ => self.model_form.status = FormStatus::Failed(error),
+                        },
+                        ModelConfigSaved(Ok(())) => match self.mode {
+                            InputMode::ModelSwitcher => self.mode = InputMode::Normal,
+                            _ => self.model_form.status = FormStatus::Saved,
+                        },
+                        McpConfig(config) => self.mcp.load_config(config),
+                        McpSaved(saved) => self.mcp.saved(saved),
+                        McpServers(servers) => self.mcp.load_servers(servers),
+                        ActiveModel(model) => {
+                            self.active_model = Some(model);
+                        },
+                        ProviderModels { provider, models } => {
+                            self.model_switcher.add_models(&provider, models);
                         },
                         End => {
                             self.streaming = false;
-                            self.ct_index = if self.ct_index > 0 { self.ct_index.saturating_add(1) } else { 0 };
+                            self.streaming_key = None;
                             let _ = self.inner_tx.send(TuiInnerAction::ForceRender).await;
                         },
                     }
@@ -157,6 +318,86 @@ impl<'a> Tui<'a> {
                         ForceRender => {
                             info!("force render");
                         }
+                        YankCode(n) => {
+                            let ct = cr.read(self.active).await;
+                            let turns = transcript(ct, self.streaming_frame(), self.generation);
+                            let turn = self.message_state.as_ref().unwrap().current_turn();
+                            let blocks = turns.get(turn).map(|turn| code_blocks(turn.frame)).unwrap_or_default();
+                            match blocks.get(n.saturating_sub(1)) {
+                                Some(code) => {
+                                    info!("yank code block {}", n);
+                                    if let Err(e) = clipboard::copy(code) {
+                                        error!("failed to copy code block: {}", e);
+                                    }
+                                }
+                                None => info!("no code block {}", n),
+                            }
+                        }
+                        Retry => {
+                            let ct = cr.read(self.active).await;
+                            let turn = self.message_state.as_ref().unwrap().current_turn();
+                            if let Some(frame) = ct.get(turn) {
+                                let latest = turn + 1 == ct.len();
+                                let message = frame.user.body.content.clone();
+                                let message_id = frame.ai.body.message_id.clone();
+                                let failed = frame.error.is_some();
+
+                                // the host drops every later turn, so only the
+                                // latest one is regenerated in place
+                                if latest && !message_id.is_empty() {
+                                    self.retry(message, message_id);
+                                } else if failed {
+                                    self.send_message(message);
+                                } else {
+                                    info!("nothing to retry");
+                                }
+                            }
+                        }
+                        EditTurn => {
+                            let ct = cr.read(self.active).await;
+                            let turn = self.message_state.as_ref().unwrap().current_turn();
+                            match ct.get(turn) {
+                                Some(frame) if !frame.user.body.message_id.is_empty() => {
+                                    let mut input = TextArea::from(frame.user.body.content.lines());
+                                    input.move_cursor(CursorMove::Bottom);
+                                    input.move_cursor(CursorMove::End);
+                                    self.input = input;
+                                    self.editing = Some(frame.user.body.message_id.clone());
+                                    self.mode = InputMode::Insert;
+                                }
+                                _ => info!("turn has no message id to edit"),
+                            }
+                        }
+                        Generation(offset) => {
+                            let ct = cr.read(self.active).await;
+                            let turn = self.message_state.as_ref().unwrap().current_turn();
+                            if let Some(frame) = ct.get(turn) {
+                                let latest = frame.generations() - 1;
+                                let current = match self.generation {
+                                    Some((shown, n)) if shown == turn => n.min(latest),
+                                    _ => latest,
+                                };
+                                let next = current.saturating_add_signed(offset).min(latest);
+                                self.generation = (next < latest).then_some((turn, next));
+                            }
+                        }
+                        NewChat => {
+                            let key = cr.registry().create().await;
+                            self.switch_chat(key);
+                        }
+                        OpenChat(id) => {
+                            if let Some(thread) = cr.registry().find(&id).await {
+                                self.switch_chat(thread.key());
+                            } else {
+                                let key = cr.registry().create().await;
+                                self.switch_chat(key);
+
+                                let tx = self.tx.clone();
+                                tokio::spawn(async move {
+                                    let _ = tx.send(UIAction::LoadChat { key, id }).await;
+                                });
+                            }
+                        }
                     }
                 },
             }
@@ -172,12 +413,14 @@ impl<'a> Tui<'a> {
     }
 
     async fn open_editor(&mut self, terminal: &mut DefaultTerminal, path: String) -> Result<()> {
+        stdout().execute(DisableMouseCapture)?;
         stdout().execute(LeaveAlternateScreen)?;
         disable_raw_mode()?;
         let editor = std::env::var("EDITOR").unwrap_or("vim".to_string());
         let status = Command::new(editor).arg(path).status().await?;
         info!("editor exited with status: {}", status);
         stdout().execute(EnterAlternateScreen)?;
+        stdout().execute(EnableMouseCapture)?;
         enable_raw_mode()?;
         terminal.clear()?;
         Ok(())
@@ -202,7 +445,7 @@ impl<'a> Tui<'a> {
             .borders(Borders::ALL)
             .border_style(style)
             .border_type(BorderType::Rounded)
-            .title("Chat")
+            .title(if self.editing.is_some() { "Edit message" } else { "Chat" })
     }
 
     #[inline]
@@ -213,41 +456,134 @@ impl<'a> Tui<'a> {
     #[inline]
     async fn handle_input_event(&mut self, event: Event) {
         match event {
-            Event::Key(e) => match self.mode {
-                InputMode::Insert => self.handle_insert_key_event(e).await,
-                InputMode::Leader => self.handle_leader_key_event(e).await,
-                InputMode::EditFile => self.handle_edit_file_key_event(e).await,
-                _ => self.handle_normal_key_event(e).await,
+            Event::Key(e) => {
+                self.notice = None;
+                match self.mode {
+                    InputMode::Insert => self.handle_insert_key_event(e).await,
+                    InputMode::Leader => self.handle_leader_key_event(e).await,
+                    InputMode::EditFile => self.handle_edit_file_key_event(e).await,
+                    InputMode::History => self.handle_history_key_event(e).await,
+                    InputMode::Diagnostics => self.handle_diagnostics_key_event(e),
+                    InputMode::Logs => self.handle_logs_key_event(e),
+                    InputMode::Search => self.handle_search_key_event(e),
+                    InputMode::ChatSearch => self.handle_chat_search_key_event(e).await,
+                    InputMode::ModelConfig => self.handle_model_config_key_event(e).await,
+                    InputMode::ModelSwitcher => self.handle_model_switcher_key_event(e).await,
+                    InputMode::Mcp => self.handle_mcp_key_event(e).await,
+                    _ => self.handle_normal_key_event(e).await,
+                }
             }
+            Event::Mouse(e) => self.handle_mouse_event(e),
             _ => (),
         }
     }
 
     async fn handle_normal_key_event(&mut self, event: KeyEvent) {
+        // vim like count prefix, e.g. `2y`
+        if let KeyCode::Char(c @ '0'..='9') = event.code
+            && (c != '0' || self.count.is_some())
+        {
+            let digit = c.to_digit(10).unwrap_or_default() as usize;
+            self.count = Some(self.count.unwrap_or_default().saturating_mul(10).saturating_add(digit));
+            return;
+        }
+
+        let count = self.count.take();
+        let pending_g = std::mem::take(&mut self.pending_g);
         match event.code {
+            KeyCode::Char('c') if event.modifiers.contains(KeyModifiers::CONTROL) && self.streaming => {
+                let tx = self.tx.clone();
+                tokio::spawn(async move {
+                    let _ = tx.send(UIAction::Abort).await;
+                });
+            }
             KeyCode::Char('q') => self.quit = true,
-            KeyCode::Char('i' | 'a') => {
-                if !self.streaming {
-                    self.mode = InputMode::Insert;
-                }
+            KeyCode::Char('i' | 'a') if !self.streaming => {
+                self.mode = InputMode::Insert;
+            }
+            KeyCode::Char('j') | KeyCode::Down => {
+                self.message_state.as_mut().unwrap().scroll_down(count.unwrap_or(1));
+            }
+            KeyCode::Char('k') | KeyCode::Up => {
+                self.message_state.as_mut().unwrap().scroll_up(count.unwrap_or(1));
+            }
+            KeyCode::Char('d') if event.modifiers.contains(KeyModifiers::CONTROL) => {
+                let state = self.message_state.as_mut().unwrap();
+                state.scroll_down(state.half_page() * count.unwrap_or(1));
+            }
+            KeyCode::Char('u') if event.modifiers.contains(KeyModifiers::CONTROL) => {
+                let state = self.message_state.as_mut().unwrap();
+                state.scroll_up(state.half_page() * count.unwrap_or(1));
+            }
+            KeyCode::Char('f') if event.modifiers.contains(KeyModifiers::CONTROL) => {
+                let state = self.message_state.as_mut().unwrap();
+                state.scroll_down(state.page() * count.unwrap_or(1));
+            }
+            KeyCode::Char('b') if event.modifiers.contains(KeyModifiers::CONTROL) => {
+                let state = self.message_state.as_mut().unwrap();
+                state.scroll_up(state.page() * count.unwrap_or(1));
+            }
+            KeyCode::Char('g') if pending_g => {
+                self.message_state.as_mut().unwrap().scroll_top();
+            }
+            KeyCode::Char('g') => self.pending_g = true,
+            KeyCode::Char('G') => {
+                self.message_state.as_mut().unwrap().scroll_bottom();
+            }
+            KeyCode::Char(c @ ('/' | '?')) => {
+                self.mode = InputMode::Search;
+                self.search_query.clear();
+                self.search_options.backward = c == '?';
+                self.search_invalid = false;
+                self.message_state.as_mut().unwrap().begin_search();
+            }
+            KeyCode::Esc => {
+                self.message_state.as_mut().unwrap().clear_search();
+            }
+            KeyCode::Char('}') => {
+                self.message_state.as_mut().unwrap().jump_paragraph(count.unwrap_or(1) as isize);
+            }
+            KeyCode::Char('{') => {
+                self.message_state.as_mut().unwrap().jump_paragraph(-(count.unwrap_or(1) as isize));
+            }
+            KeyCode::Char('h') => {
+                self.message_state.as_mut().unwrap().scroll_left();
+            }
+            KeyCode::Char('l') => {
+                self.message_state.as_mut().unwrap().scroll_right();
+            }
+            KeyCode::Char('e') if !self.streaming => {
+                let _ = self.inner_tx.send(TuiInnerAction::EditTurn).await;
+            }
+            KeyCode::Char('r') if !self.streaming => {
+                let _ = self.inner_tx.send(TuiInnerAction::Retry).await;
             }
-            KeyCode::Char('j') => {
-                self.message_state.as_mut().unwrap().scroll_down();
+            KeyCode::Char('<') => {
+                let _ = self.inner_tx.send(TuiInnerAction::Generation(-1)).await;
             }
-            KeyCode::Char('k') => {
-                self.message_state.as_mut().unwrap().scroll_up();
+            KeyCode::Char('>') => {
+                let _ = self.inner_tx.send(TuiInnerAction::Generation(1)).await;
+            }
+            KeyCode::Char('t') => {
+                self.message_state.as_mut().unwrap().toggle_tools();
+            }
+            KeyCode::Char('y') => {
+                let _ = self.inner_tx.send(TuiInnerAction::YankCode(count.unwrap_or(1))).await;
             }
             KeyCode::Char('n') if event.modifiers.contains(KeyModifiers::CONTROL) => {
-                self.message_state.as_mut().unwrap().reset();
-                self.ct_index = self.ct_index.saturating_sub(1);
+                self.message_state.as_mut().unwrap().next_turn();
             }
             KeyCode::Char('p') if event.modifiers.contains(KeyModifiers::CONTROL) => {
-                self.message_state.as_mut().unwrap().reset();
-                let index = self.ct_index.saturating_add(1);
-                if index < self.thread_len {
-                    self.ct_index = index;
-                }
+                self.message_state.as_mut().unwrap().previous_turn();
+            }
+            KeyCode::Char('n') => {
+                self.message_state.as_mut().unwrap().search_next(false);
+            }
+            KeyCode::Char('N') => {
+                self.message_state.as_mut().unwrap().search_next(true);
             }
+            KeyCode::Char(']') => self.cycle_chat(1),
+            KeyCode::Char('[') => self.cycle_chat(-1),
             KeyCode::Char(' ') => {
                 self.mode = InputMode::Leader;
             }
@@ -257,7 +593,13 @@ impl<'a> Tui<'a> {
 
     async fn handle_insert_key_event(&mut self, event: KeyEvent) {
         match event.code {
-            KeyCode::Esc => self.mode = InputMode::Normal,
+            KeyCode::Esc => {
+                self.mode = InputMode::Normal;
+                // an edit is dropped rather than kept for a new turn
+                if self.editing.take().is_some() {
+                    self.input = TextArea::default();
+                }
+            }
             KeyCode::Enter => {
                 if self.streaming {
                     return;
@@ -267,21 +609,13 @@ impl<'a> Tui<'a> {
                     return;
                 }
 
-                self.message_state.as_mut().unwrap().reset();
-                self.ai_message.body.content.clear();
-                self.user_message.body.content.clear();
-                self.user_message.body.content.push_str(&self.input.lines().join("\n"));
-                self.streaming = true;
                 self.mode = InputMode::Normal;
-
-                let tx = self.tx.clone();
                 let message = self.input.lines().join("\n");
                 self.input = TextArea::default();
-
-                let id = self.chat_id.clone();
-                tokio::spawn(async move {
-                    let _ = tx.send(UIAction::Chat { id, message }).await;
-                });
+                match self.editing.take() {
+                    Some(message_id) => self.edit(message, message_id),
+                    None => self.send_message(message),
+                }
             },
             _ => {
                 self.input.input(event);
@@ -294,6 +628,52 @@ impl<'a> Tui<'a> {
             KeyCode::Char('e') => {
                 self.mode = InputMode::EditFile;
             }
+            KeyCode::Char('n') => {
+                self.mode = InputMode::Normal;
+                let _ = self.inner_tx.send(TuiInnerAction::NewChat).await;
+            }
+            KeyCode::Char('b') => {
+                self.mode = InputMode::Normal;
+                self.sidebar = !self.sidebar;
+            }
+            KeyCode::Char('d') => {
+                self.mode = InputMode::Diagnostics;
+            }
+            KeyCode::Char('l') => {
+                self.mode = InputMode::Logs;
+                self.logs.poll();
+            }
+            KeyCode::Char('m') => {
+                self.mode = InputMode::ModelSwitcher;
+                self.model_switcher.reset();
+
+                let tx = self.tx.clone();
+                tokio::spawn(async move {
+                    let _ = tx.send(UIAction::LoadModelConfig).await;
+                });
+            }
+            KeyCode::Char('t') => {
+                self.mode = InputMode::Mcp;
+                self.mcp.reset();
+
+                let tx = self.tx.clone();
+                tokio::spawn(async move {
+                    let _ = tx.send(UIAction::LoadMcp).await;
+                });
+            }
+            KeyCode::Char('/') => {
+                self.mode = InputMode::ChatSearch;
+                self.chat_search.clear();
+            }
+            KeyCode::Char('h') => {
+                self.mode = InputMode::History;
+                self.chats = None;
+
+                let tx = self.tx.clone();
+                tokio::spawn(async move {
+                    let _ = tx.send(UIAction::ListChats).await;
+                });
+            }
             _ => {
                 self.mode = InputMode::Normal;
                 self.handle_normal_key_event(event).await;
@@ -305,6 +685,15 @@ impl<'a> Tui<'a> {
         self.mode = InputMode::Normal;
         match event.code {
             KeyCode::Char('m') => {
+                self.mode = InputMode::ModelConfig;
+                self.model_form.reset();
+
+                let tx = self.tx.clone();
+                tokio::spawn(async move {
+                    let _ = tx.send(UIAction::LoadModelConfig).await;
+                });
+            }
+            KeyCode::Char('M') => {
                 let _ = self.inner_tx.send(TuiInnerAction::OpenEditor(PROJECT_DIRS.host_config_dir().join(MODEL_CONFIG_FILE).to_string_lossy().to_string())).await;
             }
             KeyCode::Char('s') => {
@@ -317,56 +706,528 @@ impl<'a> Tui<'a> {
     }
 }
 
-impl<'a> Drop for Tui<'a> {
-    fn drop(&mut self) {
-        ratatui::restore();
+impl Tui<'_> {
+    async fn handle_history_key_event(&mut self, event: KeyEvent) {
+        match event.code {
+            KeyCode::Esc | KeyCode::Char('q') => {
+                self.mode = InputMode::Normal;
+            }
+            KeyCode::Char('j') | KeyCode::Down => {
+                self.history_state.select_next();
+            }
+            KeyCode::Char('k') | KeyCode::Up => {
+                self.history_state.select_previous();
+            }
+            KeyCode::Enter => {
+                let Some(chat) = self.history_state.selected()
+                    .and_then(|i| self.chats.as_ref()?.get(i)) else {
+                    return;
+                };
+
+                self.mode = InputMode::Normal;
+                let _ = self.inner_tx.send(TuiInnerAction::OpenChat(chat.id.clone())).await;
+            }
+            _ => (),
+        }
     }
 }
 
-#[inline]
-fn get_chat_to_render<'b>(streaming: bool, index: usize, def: (&'b UserMessage, &'b AIMessage), ct: &'b [Arc<MessageFrame>]) -> (&'b UserMessage, &'b AIMessage) {
-    if ct.is_empty() {
-        return def;
+impl Tui<'_> {
+    async fn handle_chat_search_key_event(&mut self, event: KeyEvent) {
+        let ctrl = event.modifiers.contains(KeyModifiers::CONTROL);
+        match event.code {
+            KeyCode::Esc => {
+                self.mode = InputMode::Normal;
+            }
+            KeyCode::Down | KeyCode::Tab => self.chat_search.list.select_next(),
+            KeyCode::Char('n') if ctrl => self.chat_search.list.select_next(),
+            KeyCode::Up | KeyCode::BackTab => self.chat_search.list.select_previous(),
+            KeyCode::Char('p') if ctrl => self.chat_search.list.select_previous(),
+            KeyCode::Enter => {
+                let Some(hit) = self.chat_search.selected() else {
+                    return;
+                };
+
+                self.mode = InputMode::Normal;
+                let _ = self.inner_tx.send(TuiInnerAction::OpenChat(hit.chat.id.clone())).await;
+            }
+            KeyCode::Backspace => {
+                self.chat_search.query.pop();
+                self.update_chat_search();
+            }
+            KeyCode::Char('u') if ctrl => {
+                self.chat_search.query.clear();
+                self.update_chat_search();
+            }
+            KeyCode::Char(c) if !ctrl => {
+                self.chat_search.query.push(c);
+                self.update_chat_search();
+            }
+            _ => (),
+        }
+    }
+
+    async fn handle_model_config_key_event(&mut self, event: KeyEvent) {
+        if self.model_form.editing() {
+            self.model_form.input(event);
+            return;
+        }
+
+        match event.code {
+            KeyCode::Esc | KeyCode::Char('q') => {
+                self.mode = InputMode::Normal;
+            }
+            KeyCode::Char('j') | KeyCode::Down => self.model_form.move_cursor(1),
+            KeyCode::Char('k') | KeyCode::Up => self.model_form.move_cursor(-1),
+            KeyCode::Char('l') | KeyCode::Right => self.model_form.cycle(1),
+            KeyCode::Char('h') | KeyCode::Left => self.model_form.cycle(-1),
+            KeyCode::Enter | KeyCode::Char(' ') => self.model_form.activate(),
+            KeyCode::Char('a') => self.model_form.add_provider(),
+            KeyCode::Char('d') => self.model_form.remove_provider(),
+            KeyCode::Char('s') => {
+                if let Some(config) = self.model_form.submit() {
+                    let tx = self.tx.clone();
+                    tokio::spawn(async move {
+                        let _ = tx.send(UIAction::SaveModelConfig(config)).await;
+                    });
+                }
+            }
+            _ => (),
+        }
+    }
+
+    async fn handle_model_switcher_key_event(&mut self, event: KeyEvent) {
+        let ctrl = event.modifiers.contains(KeyModifiers::CONTROL);
+        match event.code {
+            KeyCode::Esc => {
+                self.mode = InputMode::Normal;
+            }
+            KeyCode::Down | KeyCode::Tab => self.model_switcher.list.select_next(),
+            KeyCode::Char('n') if ctrl => self.model_switcher.list.select_next(),
+            KeyCode::Up | KeyCode::BackTab => self.model_switcher.list.select_previous(),
+            KeyCode::Char('p') if ctrl => self.model_switcher.list.select_previous(),
+            KeyCode::Enter => match self.model_switcher.switch() {
+                Some(config) => {
+                    let tx = self.tx.clone();
+                    tokio::spawn(async move {
+                        let _ = tx.send(UIAction::SaveModelConfig(config)).await;
+                    });
+                }
+                None => self.mode = InputMode::Normal,
+            },
+            KeyCode::Backspace => {
+                self.model_switcher.query.pop();
+                self.model_switcher.update();
+            }
+            KeyCode::Char('u') if ctrl => {
+                self.model_switcher.query.clear();
+                self.model_switcher.update();
+            }
+            KeyCode::Char(c) if !ctrl => {
+                self.model_switcher.query.push(c);
+                self.model_switcher.update();
+            }
+            _ => (),
+        }
+    }
+
+    async fn handle_mcp_key_event(&mut self, event: KeyEvent) {
+        if let Some(form) = self.mcp.form.as_mut() {
+            if form.editing() {
+                form.input(event);
+                return;
+            }
+
+            match event.code {
+                KeyCode::Esc | KeyCode::Char('q') => self.mcp.form = None,
+                KeyCode::Char('j') | KeyCode::Down => form.move_cursor(1),
+                KeyCode::Char('k') | KeyCode::Up => form.move_cursor(-1),
+                KeyCode::Char('l') | KeyCode::Right => form.cycle(1),
+                KeyCode::Char('h') | KeyCode::Left => form.cycle(-1),
+                KeyCode::Enter | KeyCode::Char(' ') => form.activate(),
+                KeyCode::Char('s') => {
+                    if let Some(config) = self.mcp.submit_form() {
+                        let tx = self.tx.clone();
+                        tokio::spawn(async move {
+                            let _ = tx.send(UIAction::SaveMcpConfig(config)).await;
+                        });
+                    }
+                }
+                _ => (),
+            }
+            return;
+        }
+
+        match event.code {
+            KeyCode::Esc | KeyCode::Char('q') => {
+                self.mode = InputMode::Normal;
+            }
+            KeyCode::Char('j') | KeyCode::Down => self.mcp.move_cursor(1),
+            KeyCode::Char('k') | KeyCode::Up => self.mcp.move_cursor(-1),
+            KeyCode::Char('l') | KeyCode::Right | KeyCode::Tab => self.mcp.focus_tools(),
+            KeyCode::Char('h') | KeyCode::Left | KeyCode::BackTab => self.mcp.focus_servers(),
+            KeyCode::Enter | KeyCode::Char(' ') => {
+                if let Some(config) = self.mcp.toggle() {
+                    let tx = self.tx.clone();
+                    tokio::spawn(async move {
+                        let _ = tx.send(UIAction::SaveMcpConfig(config)).await;
+                    });
+                }
+            }
+            KeyCode::Char('a') => self.mcp.open_form(),
+            KeyCode::Char('r') => {
+                self.mcp.reset();
+
+                let tx = self.tx.clone();
+                tokio::spawn(async move {
+                    let _ = tx.send(UIAction::LoadMcp).await;
+                });
+            }
+            _ => (),
+        }
+    }
+
+    fn update_chat_search(&mut self) {
+        if let Some(index) = &self.index {
+            self.chat_search.update(index);
+        }
+    }
+}
+
+impl Tui<'_> {
+    /// The frame being streamed, if it belongs to the chat on screen.
+    fn streaming_frame(&self) -> Option<&MessageFrame> {
+        (self.streaming && self.streaming_key == Some(self.active)).then_some(&self.frame)
+    }
+
+    /// Start streaming the answer to `message` in the active chat.
+    fn send_message(&mut self, message: String) {
+        let key = self.active;
+        self.start_streaming(&message, UIAction::Chat { key, message: message.clone() });
+    }
+
+    /// Start streaming a new answer to the latest turn of the active chat,
+    /// whose ai message is `message_id`.
+    fn retry(&mut self, message: String, message_id: String) {
+        let key = self.active;
+        self.start_streaming(&message, UIAction::Retry { key, message_id });
+    }
+
+    /// Replace the user message `message_id` of the active chat by `message`,
+    /// dropping the turns after it, and stream the answer.
+    fn edit(&mut self, message: String, message_id: String) {
+        let key = self.active;
+        self.start_streaming(&message, UIAction::Edit { key, message_id, message: message.clone() });
+    }
+
+    fn start_streaming(&mut self, message: &str, action: UIAction) {
+        self.message_state.as_mut().unwrap().reset();
+        self.frame = MessageFrame::default();
+        self.frame.user.body.content.push_str(message);
+        self.streaming = true;
+        self.streaming_key = Some(self.active);
+        self.generation = None;
+
+        let tx = self.tx.clone();
+        tokio::spawn(async move {
+            let _ = tx.send(action).await;
+        });
+    }
+
+    fn handle_search_key_event(&mut self, event: KeyEvent) {
+        let state = self.message_state.as_mut().unwrap();
+        let alt = event.modifiers.contains(KeyModifiers::ALT);
+        match event.code {
+            KeyCode::Esc => {
+                self.mode = InputMode::Normal;
+                state.cancel_search();
+                return;
+            }
+            KeyCode::Enter if self.search_query.is_empty() || self.search_invalid => {
+                self.mode = InputMode::Normal;
+                state.cancel_search();
+                return;
+            }
+            KeyCode::Enter => {
+                self.mode = InputMode::Normal;
+                state.confirm_search();
+                return;
+            }
+            KeyCode::Backspace => {
+                if self.search_query.pop().is_none() {
+                    self.mode = InputMode::Normal;
+                    state.cancel_search();
+                    return;
+                }
+            }
+            KeyCode::Char('r') if alt => self.search_options.regex = !self.search_options.regex,
+            KeyCode::Char('c') if alt => self.search_options.match_case = !self.search_options.match_case,
+            KeyCode::Char(c) => self.search_query.push(c),
+            _ => return,
+        }
+
+        self.search_invalid = state.set_search(&self.search_query, self.search_options).is_err();
+    }
+
+    /// The search prompt with the state of the search.
+    fn search_prompt(&self) -> String {
+        let mut prompt = format!("{}{}▏", if self.search_options.backward { '?' } else { '/' }, self.search_query);
+        if self.search_options.regex {
+            prompt.push_str(" [regex]");
+        }
+        if self.search_options.match_case {
+            prompt.push_str(" [case]");
+        }
+        if self.search_invalid {
+            prompt.push_str(" [invalid]");
+        } else if self.message_state.as_ref().unwrap().search_failed() {
+            prompt.push_str(" [no match]");
+        }
+        prompt
+    }
+
+    fn handle_mouse_event(&mut self, event: MouseEvent) {
+        let position = Position::new(event.column, event.row);
+        let state = self.message_state.as_mut().unwrap();
+
+        if !matches!(self.mode, InputMode::Normal | InputMode::Insert) {
+            // popups take the wheel wherever it is
+            match (self.mode, event.kind) {
+                (InputMode::History, MouseEventKind::ScrollDown) => self.history_state.select_next(),
+                (InputMode::History, MouseEventKind::ScrollUp) => self.history_state.select_previous(),
+                (InputMode::Logs, MouseEventKind::ScrollDown) => self.logs.scroll_down(WHEEL_LINES),
+                (InputMode::Logs, MouseEventKind::ScrollUp) => self.logs.scroll_up(WHEEL_LINES),
+                (InputMode::ChatSearch, MouseEventKind::ScrollDown) => self.chat_search.list.select_next(),
+                (InputMode::ChatSearch, MouseEventKind::ScrollUp) => self.chat_search.list.select_previous(),
+                (InputMode::ModelSwitcher, MouseEventKind::ScrollDown) => self.model_switcher.list.select_next(),
+                (InputMode::ModelSwitcher, MouseEventKind::ScrollUp) => self.model_switcher.list.select_previous(),
+                (InputMode::Mcp, MouseEventKind::ScrollDown) => self.mcp.move_cursor(1),
+                (InputMode::Mcp, MouseEventKind::ScrollUp) => self.mcp.move_cursor(-1),
+                _ => (),
+            }
+            return;
+        }
+
+        match event.kind {
+            MouseEventKind::ScrollUp | MouseEventKind::ScrollDown if self.input_area.contains(position) => {
+                self.input.input(event);
+            }
+            MouseEventKind::ScrollUp if self.chat_area.contains(position) => {
+                state.scroll_up(WHEEL_LINES);
+            }
+            MouseEventKind::ScrollDown if self.chat_area.contains(position) => {
+                state.scroll_down(WHEEL_LINES);
+            }
+            MouseEventKind::Down(MouseButton::Left) => {
+                state.clear_selection();
+                if self.input_area.contains(position) && !self.streaming {
+                    self.mode = InputMode::Insert;
+                } else if self.chat_area.contains(position) {
+                    self.mode = InputMode::Normal;
+                    state.select_start(event.column, event.row);
+                }
+            }
+            MouseEventKind::Drag(MouseButton::Left) => state.select_to(event.column, event.row),
+            MouseEventKind::Up(MouseButton::Left) => {
+                if let Some(text) = state.selected_text()
+                    && let Err(e) = clipboard::copy(&text)
+                {
+                    error!("failed to copy selection: {}", e);
+                }
+            }
+            _ => (),
+        }
     }
 
-    if streaming && index == 0 {
-        return def;
+    fn handle_diagnostics_key_event(&mut self, event: KeyEvent) {
+        if let KeyCode::Esc | KeyCode::Char('q' | 'd') = event.code {
+            self.mode = InputMode::Normal;
+        }
     }
 
-    let index = if index >= ct.len() { ct.len() - 1 } else { index };
-    let Some(msg) = ct.get(ct.len() - index - 1) else {
-        info!("ct index out of bounds");
-        return def;
-    };
+    fn handle_logs_key_event(&mut self, event: KeyEvent) {
+        let logs = &mut self.logs;
+        if logs.searching {
+            match event.code {
+                KeyCode::Esc => {
+                    logs.query.clear();
+                    logs.searching = false;
+                }
+                KeyCode::Enter => logs.searching = false,
+                KeyCode::Backspace => {
+                    logs.query.pop();
+                }
+                KeyCode::Char(c) => logs.query.push(c),
+                _ => (),
+            }
+            return;
+        }
 
-    msg.split_ref()
+        match event.code {
+            KeyCode::Esc | KeyCode::Char('q') => self.mode = InputMode::Normal,
+            KeyCode::Tab => logs.toggle_source(),
+            KeyCode::Char('d') if event.modifiers.contains(KeyModifiers::CONTROL) => logs.scroll_down(logs.half_page()),
+            KeyCode::Char('u') if event.modifiers.contains(KeyModifiers::CONTROL) => logs.scroll_up(logs.half_page()),
+            KeyCode::Char('j') | KeyCode::Down => logs.scroll_down(1),
+            KeyCode::Char('k') | KeyCode::Up => logs.scroll_up(1),
+            KeyCode::Char('g') => logs.scroll_top(),
+            KeyCode::Char('G') => logs.scroll_bottom(),
+            KeyCode::Char('f') => logs.follow = !logs.follow,
+            KeyCode::Char('v') => logs.cycle_level(),
+            KeyCode::Char('/') => {
+                logs.query.clear();
+                logs.searching = true;
+            }
+            _ => (),
+        }
+    }
+
+    fn switch_chat(&mut self, key: ThreadKey) {
+        if key == self.active {
+            return;
+        }
+
+        let state = self.message_state.as_mut().unwrap();
+        self.views.insert(self.active, state.view());
+
+        let view = self.views.remove(&key).unwrap_or_default();
+        self.active = key;
+        self.generation = None;
+        state.set_view(view);
+    }
+
+    fn cycle_chat(&mut self, offset: isize) {
+        let len = self.summaries.len() as isize;
+        let Some(index) = self.summaries.iter().position(|c| c.key == self.active) else {
+            return;
+        };
+
+        if len > 1 {
+            let index = (index as isize + offset).rem_euclid(len) as usize;
+            self.switch_chat(self.summaries[index].key);
+        }
+    }
+}
+
+impl<'a> Drop for Tui<'a> {
+    fn drop(&mut self) {
+        let _ = stdout().execute(DisableMouseCapture);
+        ratatui::restore();
+    }
+}
+
+/// The turns of a chat as they are shown, the one being streamed last.
+fn transcript<'b>(ct: &'b [Arc<MessageFrame>], streaming: Option<&'b MessageFrame>, generation: Option<(usize, usize)>) -> Vec<Turn<'b>> {
+    let mut turns: Vec<Turn> = ct.iter()
+        .enumerate()
+        .map(|(i, frame)| {
+            let total = frame.generations();
+            let shown = generation.and_then(|(turn, n)| (turn == i && n + 1 < total).then_some(n));
+            Turn {
+                frame: frame.generation(shown),
+                generation: (total > 1).then(|| (shown.map_or(total, |n| n + 1), total)),
+            }
+        })
+        .collect();
+    turns.extend(streaming.map(|frame| Turn { frame, generation: None }));
+    turns
 }
 
 #[inline]
-fn layout(area: Rect) -> [Rect; 3] {
-    let [chat, input, status_bar] = Layout::vertical([
+fn layout(area: Rect, sidebar: bool) -> [Rect; 4] {
+    let [main, status_bar] = Layout::vertical([
         Constraint::Min(1),
-        Constraint::Length(5),
         Constraint::Length(2),
     ])
     .areas(area);
 
-    [chat, input, status_bar]
+    let sidebar_width = if sidebar { SIDEBAR_WIDTH } else { 0 };
+    let [sidebar, main] = Layout::horizontal([
+        Constraint::Length(sidebar_width),
+        Constraint::Min(1),
+    ])
+    .areas(main);
+
+    let [chat, input] = Layout::vertical([
+        Constraint::Min(1),
+        Constraint::Length(5),
+    ])
+    .areas(main);
+
+    [sidebar, chat, input, status_bar]
 }
 
 fn draw(frame: &mut Frame, state: &mut Tui, current_ct: &[Arc<MessageFrame>]) {
     let area = frame.area();
 
-    let [chat, input, status_bar] = layout(area);
+    let [sidebar, chat, input, status_bar] = layout(area, state.sidebar);
+    state.chat_area = chat;
+    state.input_area = input;
 
     // prepare message state
+    let streaming = state.streaming && state.streaming_key == Some(state.active);
+    let turns = transcript(current_ct, streaming.then_some(&state.frame), state.generation);
     let msg_state = state.message_state.as_mut().unwrap();
     msg_state.set_viewport(chat);
-    let chat_buf = (&state.user_message, &state.ai_message);
-    let (user, ai) = get_chat_to_render(state.streaming, state.ct_index, chat_buf, current_ct);
-    msg_state.pre_render(user, ai);
+    msg_state.pre_render(&turns);
 
+    frame.render_stateful_widget_ref(Message { streaming }, chat, msg_state);
     frame.render_widget(&state.input, input);
-    frame.render_widget(StatusBar { mode: state.mode }, status_bar);
-    frame.render_stateful_widget_ref(Message { streaming: state.streaming }, chat, msg_state);
+    let prompt = matches!(state.mode, InputMode::Search).then(|| state.search_prompt());
+    frame.render_widget(StatusBar {
+        mode: state.mode,
+        prompt: prompt.as_deref(),
+        notice: state.notice.as_deref(),
+        streaming: state.streaming,
+        editing: state.editing.is_some(),
+        model: state.active_model.as_deref(),
+        host: &state.host,
+    }, status_bar);
+
+    if state.sidebar {
+        let sidebar_widget = Sidebar {
+            chats: &state.summaries,
+            active: state.active,
+            streaming: state.streaming_key,
+        };
+        frame.render_widget(sidebar_widget, sidebar);
+    }
+
+    if let InputMode::History = state.mode {
+        let history = History {
+            chats: state.chats.as_deref().unwrap_or_default(),
+            loading: state.chats.is_none(),
+        };
+        frame.render_stateful_widget(history, area, &mut state.history_state);
+    }
+
+    if let InputMode::Diagnostics = state.mode {
+        let diagnostics = Diagnostics {
+            health: &state.host,
+            pid: state.host_pid,
+            started: state.host_started,
+            messages: &state.host_messages,
+        };
+        frame.render_widget(diagnostics, area);
+    }
+
+    if let InputMode::Logs = state.mode {
+        frame.render_stateful_widget(Logs, area, &mut state.logs);
+    }
+
+    if let InputMode::ModelSwitcher = state.mode {
+        frame.render_stateful_widget(ModelSwitcher, area, &mut state.model_switcher);
+    }
+
+    if let InputMode::Mcp = state.mode {
+        frame.render_stateful_widget(Mcp, area, &mut state.mcp);
+    }
+
+    if let InputMode::ModelConfig = state.mode {
+        frame.render_stateful_widget(ModelForm, area, &mut state.model_form);
+    }
+
+    if let InputMode::ChatSearch = state.mode {
+        frame.render_stateful_widget(ChatSearch { available: state.index.is_some() }, area, &mut state.chat_search);
+    }
 }
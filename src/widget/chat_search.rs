@@ -0,0 +1,124 @@
+use ratatui::{
+    buffer::Buffer,
+    layout::{Constraint, Layout, Rect},
+    style::{Color, Modifier, Style, Stylize},
+    text::{Line, Span, Text},
+    widgets::{
+        Block,
+        BorderType,
+        Borders,
+        Clear,
+        List,
+        ListItem,
+        ListState,
+        StatefulWidget,
+        Widget,
+    },
+};
+use tracing::error;
+
+use crate::index::{ChatHit, ChatIndex};
+
+use super::centered_rect;
+
+/// Chats listed for a query.
+const MAX_HITS: usize = 50;
+
+/// The query typed in the search popup and the chats it found.
+#[derive(Debug, Default)]
+pub struct ChatSearchState {
+    pub query: String,
+    hits: Vec<ChatHit>,
+    pub list: ListState,
+}
+
+impl ChatSearchState {
+    pub fn clear(&mut self) {
+        self.query.clear();
+        self.hits.clear();
+        self.list.select(None);
+    }
+
+    /// Search the index again for the current query.
+    pub fn update(&mut self, index: &ChatIndex) {
+        self.hits = index.search(&self.query, MAX_HITS).unwrap_or_else(|e| {
+            error!("Failed to search chats: {}", e);
+            vec![]
+        });
+        self.list.select(if self.hits.is_empty() { None } else { Some(0) });
+    }
+
+    pub fn selected(&self) -> Option<&ChatHit> {
+        self.hits.get(self.list.selected()?)
+    }
+}
+
+pub struct ChatSearch {
+    /// The index could be opened.
+    pub available: bool,
+}
+
+impl StatefulWidget for ChatSearch {
+    type State = ChatSearchState;
+
+    fn render(self, area: Rect, buf: &mut Buffer, state: &mut Self::State) {
+        let area = centered_rect(area, 70, 70);
+        Clear.render(area, buf);
+
+        let block = Block::default()
+            .borders(Borders::ALL)
+            .border_type(BorderType::Rounded)
+            .border_style(Style::default().fg(Color::Blue))
+            .title("Search chats");
+        let inner = block.inner(area);
+        block.render(area, buf);
+
+        let [prompt, results] = Layout::vertical([Constraint::Length(2), Constraint::Min(0)]).areas(inner);
+        Line::from(vec!["> ".cyan(), format!("{}▏", state.query).into()]).render(prompt, buf);
+
+        if state.hits.is_empty() {
+            let hint = if !self.available {
+                "the chat index could not be opened, see the logs"
+            } else if state.query.trim().is_empty() {
+                "type to search the titles and messages of every chat"
+            } else {
+                "no matching chats"
+            };
+            Line::from(hint.dark_gray()).render(results, buf);
+            return;
+        }
+
+        let items = state.hits.iter().map(|hit| {
+            let date = hit.chat.created_at.as_deref()
+                .and_then(|date| date.get(..10))
+                .unwrap_or_default();
+            let title = Line::from(vec![
+                format!("{} ", date).dark_gray(),
+                hit.chat.title.as_str().bold(),
+            ]);
+
+            ListItem::new(Text::from(vec![title, snippet(hit), Line::default()]))
+        });
+
+        let list = List::new(items)
+            .highlight_style(Style::default().bg(Color::DarkGray));
+        StatefulWidget::render(list, results, buf, &mut state.list);
+    }
+}
+
+/// The snippet of a hit with its matched words marked.
+fn snippet(hit: &ChatHit) -> Line<'_> {
+    let style = Style::default().fg(Color::Gray);
+    let mut spans = vec![Span::raw("  ")];
+    let mut last = 0;
+    for range in &hit.highlights {
+        let (Some(before), Some(matched)) = (hit.snippet.get(last..range.start), hit.snippet.get(range.clone())) else {
+            continue;
+        };
+        spans.push(Span::styled(before, style));
+        spans.push(Span::styled(matched, Style::default().fg(Color::Black).bg(Color::Yellow).add_modifier(Modifier::BOLD)));
+        last = range.end;
+    }
+    spans.push(Span::styled(&hit.snippet[last..], style));
+    Line::from(spans)
+}
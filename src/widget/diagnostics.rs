@@ -0,0 +1,102 @@
+use std::{collections::VecDeque, time::{Duration, Instant}};
+
+use ratatui::{
+    buffer::Buffer,
+    layout::Rect,
+    style::{Color, Style, Stylize},
+    text::{Line, Span},
+    widgets::{
+        Block,
+        BorderType,
+        Borders,
+        Clear,
+        Paragraph,
+        Widget,
+    },
+};
+
+use crate::{host::HostMessage, supervisor::HostHealth};
+
+use super::centered_rect;
+
+/// Host state and the latest status bus messages.
+pub struct Diagnostics<'a> {
+    pub health: &'a HostHealth,
+    pub pid: Option<u32>,
+    pub started: Option<Instant>,
+    /// Oldest first.
+    pub messages: &'a VecDeque<HostMessage>,
+}
+
+impl Widget for Diagnostics<'_> {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let area = centered_rect(area, 70, 70);
+        Clear.render(area, buf);
+
+        let block = Block::default()
+            .borders(Borders::ALL)
+            .border_type(BorderType::Rounded)
+            .border_style(Style::default().fg(Color::Blue))
+            .title("Host");
+        let inner = block.inner(area);
+        block.render(area, buf);
+
+        let latest = self.messages.back();
+        let status = latest.map(|message| &message.status);
+        let (health, health_style) = match self.health {
+            HostHealth::Connecting => ("connecting".to_string(), Style::default().fg(Color::DarkGray)),
+            HostHealth::Up { endpoint, .. } => (format!("up at {}", endpoint), Style::default().fg(Color::Green)),
+            HostHealth::Restarting { attempt, .. } => (format!("restarting, attempt {}", attempt), Style::default().fg(Color::Red)),
+        };
+        let listen = latest
+            .and_then(|message| message.listen())
+            .map(|(ip, port)| format!("{}:{}", ip, port));
+
+        let mut lines = vec![
+            field("health", Span::styled(health, health_style)),
+            field("state", status.map(|s| s.state.as_str()).unwrap_or("-").into()),
+            field("last error", match status.and_then(|s| s.last_error.as_deref()) {
+                Some(error) => error.red(),
+                None => "-".into(),
+            }),
+            field("error code", status.and_then(|s| s.error_code.as_deref()).unwrap_or("-").into()),
+            field("listen", listen.unwrap_or_else(|| "-".to_string()).into()),
+            field("uptime", self.started.map(|t| uptime(t.elapsed())).unwrap_or_else(|| "-".to_string()).into()),
+            field("pid", self.pid.map(|pid| pid.to_string()).unwrap_or_else(|| "-".to_string()).into()),
+            Line::default(),
+            Line::from("bus messages".bold()),
+        ];
+
+        // newest at the bottom, dropping the oldest ones that do not fit
+        let room = (inner.height as usize).saturating_sub(lines.len());
+        let skip = self.messages.len().saturating_sub(room);
+        if self.messages.is_empty() {
+            lines.push(Line::from("none".dark_gray()));
+        }
+        for message in self.messages.iter().skip(skip) {
+            let mut spans = vec![
+                format!("{} ", message.timestamp).dark_gray(),
+                message.status.state.clone().into(),
+            ];
+            if let Some(error) = &message.status.last_error {
+                spans.push(format!(" {}", error).red());
+            }
+            lines.push(Line::from(spans));
+        }
+
+        Paragraph::new(lines).render(inner, buf);
+    }
+}
+
+fn field<'a>(name: &'a str, value: Span<'a>) -> Line<'a> {
+    Line::from(vec![format!("{:<12}", name).dark_gray(), value])
+}
+
+fn uptime(elapsed: Duration) -> String {
+    let secs = elapsed.as_secs();
+    match secs {
+        0..60 => format!("{}s", secs),
+        60..3600 => format!("{}m {}s", secs / 60, secs % 60),
+        _ => format!("{}h {}m", secs / 3600, secs % 3600 / 60),
+    }
+}
@@ -0,0 +1,60 @@
+use std::sync::LazyLock;
+
+use ratatui::{
+    style::{Color, Modifier, Style},
+    text::Span,
+};
+use syntect::{
+    easy::HighlightLines,
+    highlighting::{FontStyle, Theme, ThemeSet},
+    parsing::SyntaxSet,
+    util::LinesWithEndings,
+};
+
+const THEME: &str = "base16-ocean.dark";
+
+static SYNTAX_SET: LazyLock<SyntaxSet> = LazyLock::new(SyntaxSet::load_defaults_newlines);
+static THEME_SET: LazyLock<ThemeSet> = LazyLock::new(ThemeSet::load_defaults);
+
+#[inline]
+fn theme() -> &'static Theme {
+    &THEME_SET.themes[THEME]
+}
+
+/// Highlight `code` written in `lang`, one list of spans per line.
+///
+/// Unknown languages fall back to a single plain style.
+pub fn highlight(code: &str, lang: &str) -> Vec<Vec<Span<'static>>> {
+    let syntax = SYNTAX_SET.find_syntax_by_token(lang);
+    let Some(syntax) = syntax.filter(|_| !lang.is_empty()) else {
+        return code.lines()
+            .map(|line| vec![Span::styled(line.to_string(), Style::default().fg(Color::Cyan))])
+            .collect();
+    };
+
+    let mut highlighter = HighlightLines::new(syntax, theme());
+    LinesWithEndings::from(code)
+        .map(|line| match highlighter.highlight_line(line, &SYNTAX_SET) {
+            Ok(ranges) => ranges.into_iter()
+                .map(|(style, text)| Span::styled(text.trim_end_matches(['\n', '\r']).to_string(), convert(style)))
+                .filter(|span| !span.content.is_empty())
+                .collect(),
+            Err(_) => vec![Span::raw(line.trim_end_matches(['\n', '\r']).to_string())],
+        })
+        .collect()
+}
+
+fn convert(style: syntect::highlighting::Style) -> Style {
+    let fg = style.foreground;
+    let mut out = Style::default().fg(Color::Rgb(fg.r, fg.g, fg.b));
+    if style.font_style.contains(FontStyle::BOLD) {
+        out = out.add_modifier(Modifier::BOLD);
+    }
+    if style.font_style.contains(FontStyle::ITALIC) {
+        out = out.add_modifier(Modifier::ITALIC);
+    }
+    if style.font_style.contains(FontStyle::UNDERLINE) {
+        out = out.add_modifier(Modifier::UNDERLINED);
+    }
+    out
+}
@@ -0,0 +1,66 @@
+use ratatui::{
+    buffer::Buffer,
+    layout::Rect,
+    style::{Color, Style, Stylize},
+    text::Line,
+    widgets::{
+        Block,
+        BorderType,
+        Borders,
+        Clear,
+        List,
+        ListItem,
+        ListState,
+        StatefulWidget,
+        Widget,
+    },
+};
+
+use crate::client::ChatInfo;
+
+use super::centered_rect;
+
+pub struct History<'a> {
+    pub chats: &'a [ChatInfo],
+    pub loading: bool,
+}
+
+impl StatefulWidget for History<'_> {
+    type State = ListState;
+
+    fn render(self, area: Rect, buf: &mut Buffer, state: &mut Self::State) {
+        let area = centered_rect(area, 60, 60);
+        Clear.render(area, buf);
+
+        let title = if self.loading { "History (loading...)" } else { "History" };
+        let block = Block::default()
+            .borders(Borders::ALL)
+            .border_type(BorderType::Rounded)
+            .border_style(Style::default().fg(Color::Blue))
+            .title(title);
+
+        if self.chats.is_empty() {
+            let hint = if self.loading { "" } else { "no chats" };
+            let inner = block.inner(area);
+            block.render(area, buf);
+            Line::from(hint.dark_gray()).render(inner, buf);
+            return;
+        }
+
+        let items = self.chats.iter().map(|chat| {
+            let date = chat.created_at.as_deref()
+                .and_then(|date| date.get(..10))
+                .unwrap_or_default();
+
+            ListItem::new(Line::from(vec![
+                format!("{} ", date).dark_gray(),
+                chat.title.as_str().into(),
+            ]))
+        });
+
+        let list = List::new(items)
+            .block(block)
+            .highlight_style(Style::default().fg(Color::Black).bg(Color::Blue));
+        StatefulWidget::render(list, area, buf, state);
+    }
+}
@@ -0,0 +1,324 @@
+use std::{
+    collections::VecDeque,
+    fmt,
+    fs::File,
+    io::{Read, Seek, SeekFrom},
+    path::PathBuf,
+    time::{Duration, Instant},
+};
+
+use ratatui::{
+    buffer::Buffer,
+    layout::Rect,
+    style::{Color, Style, Stylize},
+    text::{Line, Span},
+    widgets::{
+        Block,
+        BorderType,
+        Borders,
+        Clear,
+        Paragraph,
+        StatefulWidget,
+        Widget,
+    },
+};
+
+use crate::{logger, shared::PROJECT_DIRS};
+
+use super::centered_rect;
+
+/// Lines kept in memory, the oldest are dropped first.
+const MAX_LINES: usize = 10_000;
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    Trace,
+    Debug,
+    Info,
+    Warn,
+    Error,
+}
+
+impl Level {
+    /// Find the level name near the start of a log line, as written by both
+    /// tracing and python's logging.
+    fn parse(line: &str) -> Option<Self> {
+        let head = line.char_indices().nth(100).map_or(line, |(i, _)| &line[..i]);
+        head.split(|c: char| !c.is_ascii_alphabetic()).find_map(|word| match word {
+            "ERROR" | "CRITICAL" | "FATAL" => Some(Level::Error),
+            "WARN" | "WARNING" => Some(Level::Warn),
+            "INFO" => Some(Level::Info),
+            "DEBUG" => Some(Level::Debug),
+            "TRACE" => Some(Level::Trace),
+            _ => None,
+        })
+    }
+
+    fn style(self) -> Style {
+        match self {
+            Level::Error => Style::default().fg(Color::Red),
+            Level::Warn => Style::default().fg(Color::Yellow),
+            Level::Info => Style::default(),
+            Level::Debug | Level::Trace => Style::default().fg(Color::DarkGray),
+        }
+    }
+}
+
+impl fmt::Display for Level {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Level::Trace => "trace",
+            Level::Debug => "debug",
+            Level::Info => "info",
+            Level::Warn => "warn",
+            Level::Error => "error",
+        };
+        f.write_str(name)
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum LogSource {
+    #[default]
+    Host,
+    Tive,
+}
+
+impl LogSource {
+    fn path(self) -> PathBuf {
+        match self {
+            LogSource::Host => PROJECT_DIRS.host_log_file(),
+            LogSource::Tive => logger::log_file(),
+        }
+    }
+}
+
+/// A log file followed from disk, with the viewer's scroll and filters.
+#[derive(Debug)]
+pub struct LogsState {
+    source: LogSource,
+    lines: VecDeque<(Level, String)>,
+    /// Bytes of the file read so far.
+    offset: u64,
+    /// Trailing text not yet ended by a newline.
+    partial: String,
+    last_poll: Option<Instant>,
+    top: usize,
+    page: usize,
+    pub follow: bool,
+    pub min_level: Level,
+    pub query: String,
+    pub searching: bool,
+}
+
+impl Default for LogsState {
+    fn default() -> Self {
+        Self {
+            source: LogSource::default(),
+            lines: VecDeque::new(),
+            offset: 0,
+            partial: String::new(),
+            last_poll: None,
+            top: 0,
+            page: 0,
+            follow: true,
+            min_level: Level::Trace,
+            query: String::new(),
+            searching: false,
+        }
+    }
+}
+
+impl LogsState {
+    /// Read what was appended to the log since the last poll, at most every
+    /// `POLL_INTERVAL`.
+    pub fn poll(&mut self) {
+        if self.last_poll.is_some_and(|t| t.elapsed() < POLL_INTERVAL) {
+            return;
+        }
+        self.last_poll = Some(Instant::now());
+
+        let Ok(mut file) = File::open(self.source.path()) else {
+            return;
+        };
+        let len = file.metadata().map(|m| m.len()).unwrap_or_default();
+
+        // rotated or truncated, start over
+        if len < self.offset {
+            self.lines.clear();
+            self.partial.clear();
+            self.offset = 0;
+        }
+        if len == self.offset || file.seek(SeekFrom::Start(self.offset)).is_err() {
+            return;
+        }
+
+        let mut buf = Vec::with_capacity((len - self.offset) as usize);
+        let Ok(n) = file.read_to_end(&mut buf) else {
+            return;
+        };
+        self.offset += n as u64;
+
+        self.partial.push_str(&String::from_utf8_lossy(&buf));
+        let Some(end) = self.partial.rfind('\n') else {
+            return;
+        };
+
+        let rest = self.partial.split_off(end + 1);
+        let complete = std::mem::replace(&mut self.partial, rest);
+        for line in complete.lines() {
+            // continuation lines, e.g. tracebacks, belong to the line above
+            let level = Level::parse(line)
+                .or_else(|| self.lines.back().map(|(level, _)| *level))
+                .unwrap_or(Level::Info);
+            self.lines.push_back((level, line.to_string()));
+        }
+
+        let overflow = self.lines.len().saturating_sub(MAX_LINES);
+        self.lines.drain(..overflow);
+        self.top = self.top.saturating_sub(overflow);
+    }
+
+    /// Show the other log, reading it from the start.
+    pub fn toggle_source(&mut self) {
+        self.source = match self.source {
+            LogSource::Host => LogSource::Tive,
+            LogSource::Tive => LogSource::Host,
+        };
+        self.lines.clear();
+        self.partial.clear();
+        self.offset = 0;
+        self.top = 0;
+        self.follow = true;
+        self.last_poll = None;
+        self.poll();
+    }
+
+    /// Hide lines below the next level, wrapping back to showing everything.
+    pub fn cycle_level(&mut self) {
+        self.min_level = match self.min_level {
+            Level::Trace => Level::Debug,
+            Level::Debug => Level::Info,
+            Level::Info => Level::Warn,
+            Level::Warn => Level::Error,
+            Level::Error => Level::Trace,
+        };
+    }
+
+    pub fn scroll_up(&mut self, n: usize) {
+        self.follow = false;
+        self.top = self.top.saturating_sub(n);
+    }
+
+    pub fn scroll_down(&mut self, n: usize) {
+        self.top = self.top.saturating_add(n);
+    }
+
+    #[inline]
+    pub fn half_page(&self) -> usize {
+        (self.page / 2).max(1)
+    }
+
+    pub fn scroll_top(&mut self) {
+        self.follow = false;
+        self.top = 0;
+    }
+
+    pub fn scroll_bottom(&mut self) {
+        self.follow = true;
+    }
+
+    /// Indices of the lines passing the level filter and the search.
+    fn visible(&self) -> Vec<usize> {
+        let query = self.query.to_ascii_lowercase();
+        self.lines.iter()
+            .enumerate()
+            .filter(|(_, (level, _))| *level >= self.min_level)
+            .filter(|(_, (_, line))| query.is_empty() || line.to_ascii_lowercase().contains(&query))
+            .map(|(i, _)| i)
+            .collect()
+    }
+}
+
+pub struct Logs;
+
+impl StatefulWidget for Logs {
+    type State = LogsState;
+
+    fn render(self, area: Rect, buf: &mut Buffer, state: &mut Self::State) {
+        let area = centered_rect(area, 90, 90);
+        Clear.render(area, buf);
+
+        let name = match state.source {
+            LogSource::Host => "host",
+            LogSource::Tive => "tive",
+        };
+        let mut title = vec![format!("Logs: {} ", name).into()];
+        if state.follow {
+            title.push("[follow] ".green());
+        }
+        if state.min_level > Level::Trace {
+            title.push(format!("[{}+] ", state.min_level).yellow());
+        }
+        if !state.query.is_empty() && !state.searching {
+            title.push(format!("[/{}] ", state.query).cyan());
+        }
+
+        let block = Block::default()
+            .borders(Borders::ALL)
+            .border_type(BorderType::Rounded)
+            .border_style(Style::default().fg(Color::Blue))
+            .title(Line::from(title));
+        let mut inner = block.inner(area);
+        block.render(area, buf);
+
+        if state.searching {
+            let prompt = Rect { y: inner.bottom().saturating_sub(1), height: 1.min(inner.height), ..inner };
+            Line::from(format!("/{}▏", state.query)).render(prompt, buf);
+            inner.height = inner.height.saturating_sub(1);
+        }
+
+        let page = inner.height as usize;
+        let visible = state.visible();
+        let max_top = visible.len().saturating_sub(page);
+        state.page = page;
+        state.top = if state.follow { max_top } else { state.top.min(max_top) };
+
+        if visible.is_empty() {
+            let hint = if state.lines.is_empty() { "empty" } else { "no matching lines" };
+            Line::from(hint.dark_gray()).render(inner, buf);
+            return;
+        }
+
+        let query = state.query.to_ascii_lowercase();
+        let lines: Vec<Line> = visible.iter()
+            .skip(state.top)
+            .take(page)
+            .map(|&i| {
+                let (level, line) = &state.lines[i];
+                highlight(line, &query, level.style())
+            })
+            .collect();
+        Paragraph::new(lines).render(inner, buf);
+    }
+}
+
+/// Style a log line, marking every case-insensitive match of `query`.
+fn highlight<'a>(line: &'a str, query: &str, style: Style) -> Line<'a> {
+    if query.is_empty() {
+        return Line::styled(line, style);
+    }
+
+    // ascii lowercasing keeps byte offsets valid in the original line
+    let lower = line.to_ascii_lowercase();
+    let mut spans = vec![];
+    let mut last = 0;
+    for (start, matched) in lower.match_indices(query) {
+        spans.push(Span::styled(&line[last..start], style));
+        spans.push(Span::styled(&line[start..start + matched.len()], Style::default().fg(Color::Black).bg(Color::Yellow)));
+        last = start + matched.len();
+    }
+    spans.push(Span::styled(&line[last..], style));
+    Line::from(spans)
+}
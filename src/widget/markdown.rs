@@ -0,0 +1,920 @@
+use pulldown_cmark::{Alignment, CodeBlockKind, Event, HeadingLevel, Options, Parser, Tag, TagEnd};
+use ratatui::{
+    style::{Color, Modifier, Style, Stylize},
+    text::{Line, Span},
+};
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+use super::highlight::highlight;
+
+const QUOTE_PREFIX: &str = "│ ";
+const CODE_BORDER: &str = "│ ";
+const BULLETS: [&str; 3] = ["• ", "◦ ", "▪ "];
+const MIN_WIDTH: usize = 8;
+
+/// Renders a markdown document that keeps growing while the ai is streaming.
+///
+/// The text is cut into chunks at blank lines where every block is closed,
+/// finished chunks are rendered once and only the tail is parsed again.
+#[derive(Debug, Clone, Default)]
+pub struct Markdown {
+    source: String,
+    options: RenderOptions,
+    lines: Vec<Line<'static>>,
+    stats: RenderStats,
+    last_stats: RenderStats,
+}
+
+/// How a markdown document is laid out.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RenderOptions {
+    pub width: usize,
+    /// columns hidden on the left of code blocks, which never wrap
+    pub scroll_x: usize,
+    /// number of code blocks before this document
+    pub first_block: usize,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct RenderStats {
+    code_blocks: usize,
+    code_width: usize,
+}
+
+impl RenderStats {
+    fn add(self, other: RenderStats) -> RenderStats {
+        RenderStats {
+            code_blocks: self.code_blocks + other.code_blocks,
+            code_width: self.code_width.max(other.code_width),
+        }
+    }
+}
+
+impl Markdown {
+    pub fn render(&mut self, content: &str, options: RenderOptions) -> Vec<Line<'static>> {
+        if options != self.options || !content.starts_with(&self.source) {
+            self.source.clear();
+            self.lines.clear();
+            self.stats = RenderStats::default();
+            self.options = options;
+        }
+
+        let boundary = stable_boundary(content, self.source.len());
+        if boundary > self.source.len() {
+            let (chunk, stats) = render(&content[self.source.len()..boundary], self.chunk_options());
+            join_chunk(&mut self.lines, chunk);
+            self.source.push_str(&content[self.source.len()..boundary]);
+            self.stats = self.stats.add(stats);
+        }
+
+        let (tail, stats) = render(&content[boundary..], self.chunk_options());
+        self.last_stats = self.stats.add(stats);
+
+        let mut lines = self.lines.clone();
+        join_chunk(&mut lines, tail);
+        lines
+    }
+
+    #[inline]
+    fn chunk_options(&self) -> RenderOptions {
+        RenderOptions {
+            first_block: self.options.first_block + self.stats.code_blocks,
+            ..self.options
+        }
+    }
+
+    /// Number of code blocks in the last rendered document.
+    #[inline]
+    pub fn code_blocks(&self) -> usize {
+        self.last_stats.code_blocks
+    }
+
+    /// Width of the longest code line in the last rendered document.
+    #[inline]
+    pub fn code_width(&self) -> usize {
+        self.last_stats.code_width
+    }
+}
+
+/// The fenced and indented code blocks of a markdown document, in order.
+pub fn code_blocks(content: &str) -> Vec<String> {
+    let mut blocks = vec![];
+    let mut current: Option<String> = None;
+
+    for event in Parser::new_ext(content, parser_options()) {
+        match event {
+            Event::Start(Tag::CodeBlock(_)) => current = Some(String::new()),
+            Event::Text(text) => {
+                if let Some(code) = current.as_mut() {
+                    code.push_str(&text);
+                }
+            }
+            Event::End(TagEnd::CodeBlock) => blocks.extend(current.take()),
+            _ => {}
+        }
+    }
+
+    // a block still streaming in
+    blocks.extend(current);
+    blocks
+}
+
+fn join_chunk(lines: &mut Vec<Line<'static>>, chunk: Vec<Line<'static>>) {
+    if chunk.is_empty() {
+        return;
+    }
+
+    if !lines.is_empty() {
+        lines.push(Line::default());
+    }
+
+    lines.extend(chunk);
+}
+
+/// Offset of the last line, starting at or after `from`, that opens a new
+/// top level block: not indented, not a list item, after a blank line and
+/// outside of a code fence.
+fn stable_boundary(content: &str, from: usize) -> usize {
+    // the character and length of the open fence
+    let mut fence: Option<(char, usize)> = None;
+    let mut boundary = from;
+    let mut offset = 0;
+    let mut blank = false;
+
+    for line in content.split_inclusive('\n') {
+        // the last line may still be streaming
+        if !line.ends_with('\n') {
+            break;
+        }
+
+        let trimmed = line.trim_start();
+        match fence {
+            Some((c, len)) => {
+                // only a bare fence as long as the opening one closes it
+                if let Some((close, n)) = fence_run(trimmed)
+                    && close == c
+                    && n >= len
+                    && trimmed[n..].trim().is_empty()
+                {
+                    fence = None;
+                }
+            }
+            None => {
+                if blank && offset >= from && !line.starts_with([' ', '\t']) && !is_list_item(trimmed) && !trimmed.is_empty() {
+                    boundary = offset;
+                }
+
+                fence = fence_run(trimmed);
+            }
+        }
+
+        blank = fence.is_none() && trimmed.trim().is_empty();
+        offset += line.len();
+    }
+
+    boundary
+}
+
+/// The character and length of the code fence `line` starts with.
+fn fence_run(line: &str) -> Option<(char, usize)> {
+    let c = line.chars().next().filter(|c| matches!(c, '`' | '~'))?;
+    let len = line.chars().take_while(|&other| other == c).count();
+    (len >= 3).then_some((c, len))
+}
+
+fn is_list_item(line: &str) -> bool {
+    if line.starts_with(['-', '*', '+']) {
+        return line[1..].starts_with([' ', '\t']) || line.len() == 1;
+    }
+
+    let digits = line.chars().take_while(|c| c.is_ascii_digit()).count();
+    digits > 0 && line[digits..].starts_with(['.', ')'])
+}
+
+#[inline]
+fn parser_options() -> Options {
+    Options::ENABLE_TABLES | Options::ENABLE_STRIKETHROUGH | Options::ENABLE_TASKLISTS
+}
+
+/// Render a markdown document into lines of at most `width` columns.
+fn render(content: &str, options: RenderOptions) -> (Vec<Line<'static>>, RenderStats) {
+    let mut renderer = Renderer::new(RenderOptions {
+        width: options.width.max(MIN_WIDTH),
+        ..options
+    });
+    for event in Parser::new_ext(content, parser_options()) {
+        renderer.event(event);
+    }
+
+    renderer.finish()
+}
+
+#[derive(Debug)]
+struct ListLevel {
+    number: Option<u64>,
+    marker_width: usize,
+}
+
+#[derive(Debug)]
+struct CodeBlock {
+    lang: String,
+    text: String,
+}
+
+#[derive(Debug, Default)]
+struct Table {
+    alignments: Vec<Alignment>,
+    rows: Vec<Vec<Vec<Span<'static>>>>,
+    row: Vec<Vec<Span<'static>>>,
+    cell: Vec<Span<'static>>,
+}
+
+struct Renderer {
+    width: usize,
+    scroll_x: usize,
+    first_block: usize,
+    stats: RenderStats,
+    lines: Vec<Line<'static>>,
+    spans: Vec<Span<'static>>,
+    styles: Vec<Style>,
+    quote: usize,
+    lists: Vec<ListLevel>,
+    marker: Option<String>,
+    code: Option<CodeBlock>,
+    table: Option<Table>,
+    blank: bool,
+}
+
+impl Renderer {
+    fn new(options: RenderOptions) -> Self {
+        Self {
+            width: options.width,
+            scroll_x: options.scroll_x,
+            first_block: options.first_block,
+            stats: RenderStats::default(),
+            lines: vec![],
+            spans: vec![],
+            styles: vec![],
+            quote: 0,
+            lists: vec![],
+            marker: None,
+            code: None,
+            table: None,
+            blank: false,
+        }
+    }
+
+    fn finish(mut self) -> (Vec<Line<'static>>, RenderStats) {
+        // unclosed blocks while streaming
+        if self.code.is_some() {
+            self.end_code_block();
+        }
+        if self.table.is_some() {
+            self.end_table();
+        }
+        self.flush_line();
+        (self.lines, self.stats)
+    }
+
+    #[inline]
+    fn style(&self) -> Style {
+        self.styles.last().copied().unwrap_or_default()
+    }
+
+    fn push_style(&mut self, style: Style) {
+        self.styles.push(self.style().patch(style));
+    }
+
+    fn event(&mut self, event: Event) {
+        match event {
+            Event::Start(tag) => self.start(tag),
+            Event::End(tag) => self.end(tag),
+            Event::Text(text) => self.text(&text),
+            Event::Code(code) => {
+                let style = self.style().patch(Style::default().fg(Color::Yellow));
+                self.push_span(Span::styled(code.into_string(), style));
+            }
+            Event::InlineMath(text) | Event::DisplayMath(text) => self.text(&text),
+            Event::Html(html) | Event::InlineHtml(html) => self.text(&html),
+            Event::FootnoteReference(name) => self.text(&format!("[{}]", name)),
+            Event::SoftBreak => self.text(" "),
+            Event::HardBreak => self.flush_line(),
+            Event::Rule => {
+                self.block_start();
+                let width = self.width.saturating_sub(self.prefix_width());
+                self.spans.push(Span::styled("─".repeat(width), Style::default().fg(Color::DarkGray)));
+                self.flush_line();
+                self.block_end();
+            }
+            Event::TaskListMarker(checked) => {
+                self.push_span(Span::raw(if checked { "[x] " } else { "[ ] " }));
+            }
+        }
+    }
+
+    fn start(&mut self, tag: Tag) {
+        match tag {
+            Tag::Paragraph => self.block_start(),
+            Tag::Heading { level, .. } => {
+                self.block_start();
+                self.push_style(heading_style(level));
+            }
+            Tag::BlockQuote(_) => {
+                self.block_start();
+                self.quote += 1;
+            }
+            Tag::CodeBlock(kind) => {
+                self.block_start();
+                let lang = match kind {
+                    CodeBlockKind::Fenced(lang) => lang.split([' ', ',']).next().unwrap_or_default().to_string(),
+                    CodeBlockKind::Indented => String::new(),
+                };
+                self.code = Some(CodeBlock { lang, text: String::new() });
+            }
+            Tag::List(number) => {
+                if self.lists.is_empty() {
+                    self.block_start();
+                } else {
+                    self.flush_line();
+                }
+                self.lists.push(ListLevel { number, marker_width: 0 });
+            }
+            Tag::Item => {
+                self.flush_line();
+                let depth = self.lists.len().saturating_sub(1);
+                if let Some(level) = self.lists.last_mut() {
+                    let marker = match level.number.as_mut() {
+                        Some(n) => {
+                            let marker = format!("{}. ", n);
+                            *n += 1;
+                            marker
+                        }
+                        None => BULLETS[depth % BULLETS.len()].to_string(),
+                    };
+                    level.marker_width = marker.width();
+                    self.marker = Some(marker);
+                }
+            }
+            Tag::Emphasis => self.push_style(Style::default().add_modifier(Modifier::ITALIC)),
+            Tag::Strong => self.push_style(Style::default().add_modifier(Modifier::BOLD)),
+            Tag::Strikethrough => self.push_style(Style::default().add_modifier(Modifier::CROSSED_OUT)),
+            Tag::Link { .. } | Tag::Image { .. } => {
+                self.push_style(Style::default().fg(Color::Blue).add_modifier(Modifier::UNDERLINED));
+            }
+            Tag::Table(alignments) => {
+                self.block_start();
+                self.table = Some(Table { alignments, ..Default::default() });
+            }
+            Tag::TableHead | Tag::TableRow => {
+                if let Some(table) = self.table.as_mut() {
+                    table.row.clear();
+                }
+            }
+            Tag::TableCell => {
+                if let Some(table) = self.table.as_mut() {
+                    table.cell.clear();
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn end(&mut self, tag: TagEnd) {
+        match tag {
+            TagEnd::Paragraph => {
+                self.flush_line();
+                self.block_end();
+            }
+            TagEnd::Heading(_) => {
+                self.flush_line();
+                self.styles.pop();
+                self.block_end();
+            }
+            TagEnd::BlockQuote(_) => {
+                self.flush_line();
+                self.quote = self.quote.saturating_sub(1);
+                self.block_end();
+            }
+            TagEnd::CodeBlock => {
+                self.end_code_block();
+                self.block_end();
+            }
+            TagEnd::List(_) => {
+                self.flush_line();
+                self.lists.pop();
+                if self.lists.is_empty() {
+                    self.block_end();
+                }
+            }
+            TagEnd::Item => {
+                self.flush_line();
+                self.marker = None;
+            }
+            TagEnd::Emphasis | TagEnd::Strong | TagEnd::Strikethrough | TagEnd::Link | TagEnd::Image => {
+                self.styles.pop();
+            }
+            TagEnd::Table => {
+                self.end_table();
+                self.block_end();
+            }
+            TagEnd::TableHead | TagEnd::TableRow => {
+                if let Some(table) = self.table.as_mut() {
+                    let row = std::mem::take(&mut table.row);
+                    table.rows.push(row);
+                }
+            }
+            TagEnd::TableCell => {
+                if let Some(table) = self.table.as_mut() {
+                    let cell = std::mem::take(&mut table.cell);
+                    table.row.push(cell);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn text(&mut self, text: &str) {
+        if let Some(code) = self.code.as_mut() {
+            code.text.push_str(text);
+            return;
+        }
+
+        let span = Span::styled(text.to_string(), self.style());
+        self.push_span(span);
+    }
+
+    fn push_span(&mut self, span: Span<'static>) {
+        match self.table.as_mut() {
+            Some(table) => table.cell.push(span),
+            None => self.spans.push(span),
+        }
+    }
+
+    /// Separate top level blocks with a blank line.
+    fn block_start(&mut self) {
+        // keep a pending list marker for the first line of the block
+        if !self.spans.is_empty() {
+            self.flush_line();
+        }
+        if self.blank && self.lists.is_empty() && !self.lines.is_empty() {
+            let prefix = self.quote_prefix();
+            self.lines.push(Line::from(prefix));
+        }
+        self.blank = false;
+    }
+
+    fn block_end(&mut self) {
+        self.blank = true;
+    }
+
+    fn quote_prefix(&self) -> Vec<Span<'static>> {
+        (0..self.quote)
+            .map(|_| Span::styled(QUOTE_PREFIX, Style::default().fg(Color::DarkGray)))
+            .collect()
+    }
+
+    fn prefix_width(&self) -> usize {
+        self.quote * QUOTE_PREFIX.width() + self.lists.iter().map(|l| l.marker_width).sum::<usize>()
+    }
+
+    /// Prefixes for the first and the following lines of the current block.
+    fn prefixes(&mut self) -> (Vec<Span<'static>>, Vec<Span<'static>>) {
+        let mut first = self.quote_prefix();
+        let mut rest = self.quote_prefix();
+        let indent: usize = self.lists.iter().map(|l| l.marker_width).sum();
+
+        match self.marker.take() {
+            Some(marker) => {
+                let outer = indent - self.lists.last().map(|l| l.marker_width).unwrap_or_default();
+                first.push(Span::raw(" ".repeat(outer)));
+                first.push(Span::styled(marker, Style::default().fg(Color::Blue)));
+            }
+            None => first.push(Span::raw(" ".repeat(indent))),
+        }
+        rest.push(Span::raw(" ".repeat(indent)));
+
+        (first, rest)
+    }
+
+    fn flush_line(&mut self) {
+        if self.spans.is_empty() && self.marker.is_none() {
+            return;
+        }
+
+        let spans = std::mem::take(&mut self.spans);
+        let (first, rest) = self.prefixes();
+        let width = self.width.saturating_sub(self.prefix_width()).max(1);
+
+        for (i, line) in wrap_spans(spans, width).into_iter().enumerate() {
+            let mut prefix = if i == 0 { first.clone() } else { rest.clone() };
+            prefix.extend(line);
+            self.lines.push(Line::from(prefix));
+        }
+    }
+
+    fn end_code_block(&mut self) {
+        let Some(code) = self.code.take() else {
+            return;
+        };
+
+        self.stats.code_blocks += 1;
+        let number = self.first_block + self.stats.code_blocks;
+        let border = Style::default().fg(Color::DarkGray);
+        let (_, prefix) = self.prefixes();
+        let width = self.width.saturating_sub(self.prefix_width() + CODE_BORDER.width()).max(1);
+
+        let mut header = prefix.clone();
+        header.push(Span::styled(format!("╭─[{}]", number), border));
+        if !code.lang.is_empty() {
+            header.push(Span::styled(format!(" {}", code.lang), border.italic()));
+        }
+        self.lines.push(Line::from(header));
+
+        for spans in highlight(code.text.trim_end_matches('\n'), &code.lang) {
+            let line_width = spans_width(&spans);
+            self.stats.code_width = self.stats.code_width.max(line_width);
+
+            let mut line = prefix.clone();
+            line.push(Span::styled(CODE_BORDER, border));
+            if line_width > self.scroll_x + width {
+                line.extend(slice_spans(spans, self.scroll_x, width - 1));
+                line.push(Span::styled("›", border));
+            } else {
+                line.extend(slice_spans(spans, self.scroll_x, width));
+            }
+            self.lines.push(Line::from(line));
+        }
+
+        let mut footer = prefix;
+        footer.push(Span::styled("╰─", border));
+        self.lines.push(Line::from(footer));
+    }
+
+    fn end_table(&mut self) {
+        let Some(mut table) = self.table.take() else {
+            return;
+        };
+
+        // a row still streaming in
+        if !table.cell.is_empty() {
+            let cell = std::mem::take(&mut table.cell);
+            table.row.push(cell);
+        }
+        if !table.row.is_empty() {
+            let row = std::mem::take(&mut table.row);
+            table.rows.push(row);
+        }
+
+        let columns = table.rows.iter().map(|r| r.len()).max().unwrap_or_default();
+        if columns == 0 {
+            return;
+        }
+
+        let mut widths = vec![1; columns];
+        for row in &table.rows {
+            for (i, cell) in row.iter().enumerate() {
+                widths[i] = widths[i].max(spans_width(cell));
+            }
+        }
+
+        // shrink the widest columns until the table fits
+        let available = self.width.saturating_sub(self.prefix_width() + columns * 3 + 1);
+        while widths.iter().sum::<usize>() > available {
+            let Some(widest) = widths.iter_mut().max() else {
+                break;
+            };
+            if *widest <= 3 {
+                break;
+            }
+            *widest -= 1;
+        }
+
+        let border = Style::default().fg(Color::DarkGray);
+        let (_, prefix) = self.prefixes();
+        for (i, row) in table.rows.iter().enumerate() {
+            let mut line = prefix.clone();
+            line.push(Span::styled("│", border));
+            for (column, width) in widths.iter().enumerate() {
+                let cell = row.get(column).cloned().unwrap_or_default();
+                let cell = truncate_spans(cell, *width);
+                let pad = width - spans_width(&cell);
+                let (left, right) = match table.alignments.get(column) {
+                    Some(Alignment::Right) => (pad, 0),
+                    Some(Alignment::Center) => (pad / 2, pad - pad / 2),
+                    _ => (0, pad),
+                };
+
+                line.push(Span::raw(" ".repeat(left + 1)));
+                if i == 0 {
+                    line.extend(cell.into_iter().map(|s| s.bold()));
+                } else {
+                    line.extend(cell);
+                }
+                line.push(Span::raw(" ".repeat(right + 1)));
+                line.push(Span::styled("│", border));
+            }
+            self.lines.push(Line::from(line));
+
+            if i == 0 {
+                let mut rule = prefix.clone();
+                let cells: Vec<String> = widths.iter().map(|w| "─".repeat(w + 2)).collect();
+                rule.push(Span::styled(format!("├{}┤", cells.join("┼")), border));
+                self.lines.push(Line::from(rule));
+            }
+        }
+    }
+}
+
+fn heading_style(level: HeadingLevel) -> Style {
+    let style = Style::default().add_modifier(Modifier::BOLD);
+    match level {
+        HeadingLevel::H1 => style.fg(Color::Magenta).add_modifier(Modifier::UNDERLINED),
+        HeadingLevel::H2 => style.fg(Color::Magenta),
+        HeadingLevel::H3 => style.fg(Color::Cyan),
+        _ => style,
+    }
+}
+
+fn spans_width(spans: &[Span]) -> usize {
+    spans.iter().map(|s| s.content.width()).sum()
+}
+
+/// Keep `width` columns of `spans`, starting at column `skip`.
+fn slice_spans(spans: Vec<Span<'static>>, skip: usize, width: usize) -> Vec<Span<'static>> {
+    let mut out = vec![];
+    let mut column = 0;
+
+    for span in spans {
+        let mut content = String::new();
+        for c in span.content.chars() {
+            let w = c.width().unwrap_or_default();
+            if column >= skip && column + w <= skip + width {
+                content.push(c);
+            }
+            column += w;
+        }
+
+        if !content.is_empty() {
+            out.push(Span::styled(content, span.style));
+        }
+        if column >= skip + width {
+            break;
+        }
+    }
+
+    out
+}
+
+fn truncate_spans(spans: Vec<Span<'static>>, width: usize) -> Vec<Span<'static>> {
+    if spans_width(&spans) <= width {
+        return spans;
+    }
+
+    let mut out = vec![];
+    let mut used = 0;
+    let limit = width.saturating_sub(1);
+    'outer: for span in spans {
+        let mut content = String::new();
+        for c in span.content.chars() {
+            let w = c.width().unwrap_or_default();
+            if used + w > limit {
+                out.push(Span::styled(content, span.style));
+                break 'outer;
+            }
+            used += w;
+            content.push(c);
+        }
+        out.push(Span::styled(content, span.style));
+    }
+
+    out.push(Span::raw("…"));
+    out
+}
+
+/// Word wrap styled spans into lines of at most `width` columns.
+fn wrap_spans(spans: Vec<Span<'static>>, width: usize) -> Vec<Vec<Span<'static>>> {
+    let mut lines = vec![vec![]];
+    let mut used = 0;
+
+    for span in spans {
+        for word in span.content.split_inclusive(' ') {
+            let mut word = word;
+            let word_width = word.trim_end().width();
+
+            if used > 0 && used + word_width > width {
+                lines.push(vec![]);
+                used = 0;
+                word = word.trim_start();
+            }
+
+            // a single word longer than the line
+            if word.width() > width {
+                let mut chunk = String::new();
+                for c in word.chars() {
+                    let w = c.width().unwrap_or_default();
+                    if used + w > width {
+                        lines.last_mut().unwrap().push(Span::styled(std::mem::take(&mut chunk), span.style));
+                        lines.push(vec![]);
+                        used = 0;
+                    }
+                    used += w;
+                    chunk.push(c);
+                }
+                lines.last_mut().unwrap().push(Span::styled(chunk, span.style));
+                continue;
+            }
+
+            used += word.width();
+            lines.last_mut().unwrap().push(Span::styled(word.to_string(), span.style));
+        }
+    }
+
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DOC: &str = "# Title\n\
+        \n\
+        Some *styled* text with `code` and a [link](https://example.com).\n\
+        \n\
+        - first\n\
+        - second\n  \n  continued\n\
+        1. one\n\
+        \n\
+        ````md\n\
+        ```rust\n\
+        fn main() {}\n\
+        ```\n\
+        \n\
+        still code\n\
+        ````\n\
+        \n\
+        > quoted\n\
+        > text\n\
+        \n\
+        | name | size |\n\
+        |:-----|-----:|\n\
+        | a | 1 |\n\
+        | 世界 | 22 |\n\
+        \n\
+        ---\n\
+        \n\
+        Last paragraph, long enough to be wrapped over more than a single line.\n";
+
+    fn options(width: usize) -> RenderOptions {
+        RenderOptions { width, ..Default::default() }
+    }
+
+    fn text(lines: &[Line]) -> Vec<String> {
+        lines.iter().map(|line| line.spans.iter().map(|span| span.content.as_ref()).collect()).collect()
+    }
+
+    fn span_text(spans: &[Span]) -> String {
+        spans.iter().map(|span| span.content.as_ref()).collect()
+    }
+
+    #[test]
+    fn streamed_render_matches_whole_render() {
+        let whole = render(DOC, options(40)).0;
+
+        for step in [1, 2, 3, 7, 16] {
+            let mut markdown = Markdown::default();
+            let mut lines = vec![];
+            let mut end = 0;
+            while end < DOC.len() {
+                end = (end + step).min(DOC.len());
+                while !DOC.is_char_boundary(end) {
+                    end += 1;
+                }
+                lines = markdown.render(&DOC[..end], options(40));
+            }
+            assert_eq!(lines, whole, "streamed {} bytes at a time", step);
+        }
+    }
+
+    #[test]
+    fn render_counts_code_blocks() {
+        let mut markdown = Markdown::default();
+        markdown.render(DOC, options(40));
+        assert_eq!(markdown.code_blocks(), 1);
+        assert_eq!(code_blocks(DOC), vec!["```rust\nfn main() {}\n```\n\nstill code\n"]);
+    }
+
+    #[test]
+    fn boundary_after_closed_blocks() {
+        let content = "para\n\nnext\n";
+        assert_eq!(stable_boundary(content, 0), 6);
+        assert_eq!(stable_boundary(content, 7), 7);
+        // the last line may still grow
+        assert_eq!(stable_boundary("para\n\nnext", 0), 0);
+        // a list keeps going after a blank line
+        assert_eq!(stable_boundary("- a\n\n- b\n\n  c\n", 0), 0);
+    }
+
+    #[test]
+    fn boundary_skips_unclosed_fence() {
+        assert_eq!(stable_boundary("a\n\n```\ncode\n\nmore\n", 0), 3);
+    }
+
+    #[test]
+    fn boundary_waits_for_long_fence() {
+        let content = "a\n\n````\n```\n\nstill code\n";
+        assert_eq!(stable_boundary(content, 0), 3);
+
+        let closed = format!("{}````\n\nafter\n", content);
+        assert_eq!(stable_boundary(&closed, 0), closed.len() - "after\n".len());
+    }
+
+    #[test]
+    fn boundary_ignores_other_fence_char_and_info() {
+        // neither a tilde fence nor a fence with an info string closes ```
+        let content = "a\n\n```\n~~~\n```rust\n\nstill code\n";
+        assert_eq!(stable_boundary(content, 0), 3);
+    }
+
+    #[test]
+    fn boundary_waits_for_fence_in_list() {
+        let content = "- item\n\n  ````\n  ```\n\nstill code\n";
+        assert_eq!(stable_boundary(content, 0), 0);
+
+        let closed = format!("{}  ````\n\nafter\n", content);
+        assert_eq!(stable_boundary(&closed, 0), closed.len() - "after\n".len());
+    }
+
+    #[test]
+    fn list_items() {
+        for item in ["- a", "* a", "+\ta", "-", "1. a", "12) a", "3."] {
+            assert!(is_list_item(item), "{:?}", item);
+        }
+        for line in ["", "-a", "**bold**", "1a", "a. b", "# title"] {
+            assert!(!is_list_item(line), "{:?}", line);
+        }
+    }
+
+    #[test]
+    fn wrap_keeps_wide_chars_whole() {
+        let lines = wrap_spans(vec![Span::raw("世界世界")], 5);
+        let lines: Vec<String> = lines.iter().map(|line| span_text(line)).collect();
+        assert_eq!(lines, vec!["世界", "世界"]);
+    }
+
+    #[test]
+    fn wrap_breaks_at_words() {
+        let lines = wrap_spans(vec![Span::raw("ab "), Span::raw("cd ef")], 5);
+        let lines: Vec<String> = lines.iter().map(|line| span_text(line)).collect();
+        assert_eq!(lines, vec!["ab cd ", "ef"]);
+    }
+
+    #[test]
+    fn slice_drops_cut_wide_chars() {
+        assert_eq!(span_text(&slice_spans(vec![Span::raw("a世b")], 1, 2)), "世");
+        assert_eq!(span_text(&slice_spans(vec![Span::raw("世界")], 1, 3)), "界");
+        assert_eq!(span_text(&slice_spans(vec![Span::raw("ab"), Span::raw("cd")], 1, 2)), "bc");
+    }
+
+    #[test]
+    fn truncate_fits_wide_chars() {
+        let spans = truncate_spans(vec![Span::raw("世界世")], 4);
+        assert_eq!(span_text(&spans), "世…");
+        assert!(spans_width(&spans) <= 4);
+
+        let spans = truncate_spans(vec![Span::raw("世界")], 4);
+        assert_eq!(span_text(&spans), "世界");
+    }
+
+    #[test]
+    fn table_aligns_columns() {
+        let (lines, _) = render("| name | size |\n|:-----|-----:|\n| a | 1 |\n| 世界 | 22 |\n", options(40));
+        assert_eq!(text(&lines), vec![
+            "│ name │ size │",
+            "├──────┼──────┤",
+            "│ a    │    1 │",
+            "│ 世界 │   22 │",
+        ]);
+    }
+
+    #[test]
+    fn table_shrinks_to_width() {
+        let (lines, _) = render("| abcdefgh | ij |\n|---|---|\n| x | y |\n", options(MIN_WIDTH + 4));
+        assert_eq!(text(&lines), vec![
+            "│ ab… │ ij │",
+            "├─────┼────┤",
+            "│ x   │ y  │",
+        ]);
+    }
+
+    #[test]
+    fn table_keeps_streaming_row() {
+        let mut renderer = Renderer::new(options(40));
+        for event in Parser::new_ext("| a | b |\n|---|---|\n| 1 | 2 |\n", parser_options()) {
+            if event == Event::End(TagEnd::TableRow) {
+                break;
+            }
+            renderer.event(event);
+        }
+        assert_eq!(text(&renderer.finish().0), vec!["│ a │ b │", "├───┼───┤", "│ 1 │ 2 │"]);
+    }
+}
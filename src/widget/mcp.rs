@@ -0,0 +1,559 @@
+use crossterm::event::{KeyCode, KeyEvent};
+use ratatui::{
+    buffer::Buffer,
+    layout::{Constraint, Layout, Rect},
+    style::{Color, Modifier, Style, Stylize},
+    text::{Line, Span},
+    widgets::{
+        Block,
+        BorderType,
+        Borders,
+        Clear,
+        List,
+        ListItem,
+        ListState,
+        Paragraph,
+        StatefulWidget,
+        Widget,
+        Wrap,
+    },
+};
+use tui_textarea::TextArea;
+
+use crate::mcp_config::{McpConfig, McpServerConfig, McpServerStatus, Transport};
+
+use super::{centered_rect, model_form::FormStatus};
+
+/// Columns taken by the field names of the add server form.
+const LABEL_WIDTH: u16 = 14;
+/// Transports a server can be added with.
+const TRANSPORTS: [Transport; 2] = [Transport::Stdio, Transport::Sse];
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+enum Focus {
+    #[default]
+    Servers,
+    Tools,
+}
+
+/// A tool of the selected server.
+struct ToolRow {
+    name: String,
+    description: String,
+    enabled: bool,
+}
+
+/// The mcp servers of the host, and the changes being applied to them.
+#[derive(Debug, Default)]
+pub struct McpState {
+    /// The config as edited, ahead of the host while a change is applied.
+    config: Option<McpConfig>,
+    /// The config the host last accepted.
+    saved: Option<McpConfig>,
+    /// `None` until the host listed its servers.
+    servers: Option<Result<Vec<McpServerStatus>, String>>,
+    selected: usize,
+    tool: usize,
+    focus: Focus,
+    tools_list: ListState,
+    /// The server being added.
+    pub form: Option<ServerForm>,
+    pub status: FormStatus,
+}
+
+impl McpState {
+    /// Forget everything, waiting for the host to be read again.
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+
+    pub fn load_config(&mut self, config: Result<McpConfig, String>) {
+        match config {
+            Ok(config) => {
+                self.saved = Some(config.clone());
+                self.config = Some(config);
+                self.status = FormStatus::Ready;
+                self.move_cursor(0);
+            }
+            Err(error) => self.status = FormStatus::Failed(error),
+        }
+    }
+
+    pub fn load_servers(&mut self, servers: Result<Vec<McpServerStatus>, String>) {
+        self.servers = Some(servers);
+        self.move_cursor(0);
+    }
+
+    /// The host applied the last change, or refused it and kept its config.
+    pub fn saved(&mut self, saved: Result<(), String>) {
+        match saved {
+            Ok(()) => {
+                self.saved = self.config.clone();
+                self.status = FormStatus::Saved;
+            }
+            Err(error) => {
+                self.config = self.saved.clone();
+                self.status = FormStatus::Failed(error);
+                self.move_cursor(0);
+            }
+        }
+    }
+
+    fn server(&self) -> Option<(&String, &McpServerConfig)> {
+        self.config.as_ref()?.mcp_servers.iter().nth(self.selected)
+    }
+
+    fn status_of(&self, name: &str) -> Option<&McpServerStatus> {
+        self.servers.as_ref()?.as_ref().ok()?.iter().find(|server| server.name == name)
+    }
+
+    /// Tools the host lists for the selected server, and those hidden from
+    /// the model it may not list anymore.
+    fn tools(&self) -> Vec<ToolRow> {
+        let Some((name, server)) = self.server() else {
+            return vec![];
+        };
+
+        let mut tools: Vec<ToolRow> = self.status_of(name)
+            .map(|status| status.tools.iter()
+                .map(|tool| ToolRow {
+                    name: tool.name.clone(),
+                    description: tool.description.clone(),
+                    enabled: !server.exclude_tools.contains(&tool.name),
+                })
+                .collect())
+            .unwrap_or_default();
+        for excluded in &server.exclude_tools {
+            if tools.iter().all(|tool| tool.name != *excluded) {
+                tools.push(ToolRow { name: excluded.clone(), description: String::new(), enabled: false });
+            }
+        }
+        tools
+    }
+
+    pub fn move_cursor(&mut self, offset: isize) {
+        let servers = self.config.as_ref().map_or(0, |config| config.mcp_servers.len());
+        match self.focus {
+            Focus::Servers => {
+                let selected = self.selected.saturating_add_signed(offset).min(servers.saturating_sub(1));
+                if selected != self.selected {
+                    self.tool = 0;
+                }
+                self.selected = selected;
+            }
+            Focus::Tools => {
+                let tools = self.tools().len();
+                self.tool = self.tool.saturating_add_signed(offset).min(tools.saturating_sub(1));
+                if tools == 0 {
+                    self.focus = Focus::Servers;
+                }
+            }
+        }
+    }
+
+    pub fn focus_tools(&mut self) {
+        if !self.tools().is_empty() {
+            self.focus = Focus::Tools;
+            self.move_cursor(0);
+        }
+    }
+
+    pub fn focus_servers(&mut self) {
+        self.focus = Focus::Servers;
+    }
+
+    /// Enable or disable the server or tool under the cursor, returning the
+    /// config to push to the host.
+    pub fn toggle(&mut self) -> Option<McpConfig> {
+        if self.status == FormStatus::Saving {
+            return None;
+        }
+
+        let tool = match self.focus {
+            Focus::Servers => None,
+            Focus::Tools => Some(self.tools().get(self.tool)?.name.clone()),
+        };
+        let name = self.server()?.0.clone();
+        let config = self.config.as_mut()?;
+        let server = config.mcp_servers.get_mut(&name)?;
+        match tool {
+            Some(tool) => server.toggle_tool(&tool),
+            None => server.enabled = !server.enabled,
+        }
+
+        self.status = FormStatus::Saving;
+        Some(config.clone())
+    }
+
+    pub fn open_form(&mut self) {
+        if self.config.is_some() {
+            self.form = Some(ServerForm::default());
+            if self.status == FormStatus::Saved {
+                self.status = FormStatus::Ready;
+            }
+        }
+    }
+
+    /// Add the server of the form if it is valid, returning the config to
+    /// push to the host.
+    pub fn submit_form(&mut self) -> Option<McpConfig> {
+        if self.status == FormStatus::Saving {
+            return None;
+        }
+
+        let config = self.config.as_mut()?;
+        let (name, server) = self.form.as_mut()?.submit(config)?;
+        config.mcp_servers.insert(name.clone(), server);
+        self.selected = config.mcp_servers.keys().position(|other| *other == name).unwrap_or(0);
+        self.focus = Focus::Servers;
+        self.form = None;
+
+        self.status = FormStatus::Saving;
+        self.config.clone()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FormField {
+    Name,
+    Transport,
+    Command,
+    Args,
+    Env,
+    Url,
+}
+
+impl FormField {
+    fn label(self) -> &'static str {
+        match self {
+            FormField::Name => "name",
+            FormField::Transport => "transport",
+            FormField::Command => "command",
+            FormField::Args => "arguments",
+            FormField::Env => "environment",
+            FormField::Url => "url",
+        }
+    }
+}
+
+/// A new server, typed field by field.
+#[derive(Debug, Default)]
+pub struct ServerForm {
+    name: String,
+    transport: Transport,
+    command: String,
+    /// Separated by spaces.
+    args: String,
+    /// `KEY=value` pairs separated by spaces.
+    env: String,
+    url: String,
+    cursor: usize,
+    /// The text field being edited.
+    editor: Option<TextArea<'static>>,
+    /// Why the last submit was refused.
+    errors: Vec<String>,
+}
+
+impl ServerForm {
+    fn fields(&self) -> &'static [FormField] {
+        match self.transport {
+            Transport::Stdio => &[FormField::Name, FormField::Transport, FormField::Command, FormField::Args, FormField::Env],
+            _ => &[FormField::Name, FormField::Transport, FormField::Url],
+        }
+    }
+
+    fn field(&self) -> FormField {
+        self.fields()[self.cursor.min(self.fields().len() - 1)]
+    }
+
+    fn value_mut(&mut self, field: FormField) -> Option<&mut String> {
+        match field {
+            FormField::Name => Some(&mut self.name),
+            FormField::Command => Some(&mut self.command),
+            FormField::Args => Some(&mut self.args),
+            FormField::Env => Some(&mut self.env),
+            FormField::Url => Some(&mut self.url),
+            FormField::Transport => None,
+        }
+    }
+
+    #[inline]
+    pub fn editing(&self) -> bool {
+        self.editor.is_some()
+    }
+
+    pub fn move_cursor(&mut self, offset: isize) {
+        self.cursor = self.cursor.saturating_add_signed(offset).min(self.fields().len() - 1);
+    }
+
+    /// Step the transport, or start editing a text field.
+    pub fn activate(&mut self) {
+        let field = self.field();
+        match self.value_mut(field) {
+            Some(value) => {
+                let mut editor = TextArea::new(vec![value.clone()]);
+                editor.move_cursor(tui_textarea::CursorMove::End);
+                editor.set_cursor_line_style(Style::default());
+                self.editor = Some(editor);
+            }
+            None => self.cycle(1),
+        }
+    }
+
+    pub fn cycle(&mut self, offset: isize) {
+        if self.field() != FormField::Transport {
+            return;
+        }
+
+        let at = TRANSPORTS.iter().position(|t| *t == self.transport).unwrap_or(0) as isize;
+        self.transport = TRANSPORTS[(at + offset).rem_euclid(TRANSPORTS.len() as isize) as usize];
+    }
+
+    /// Edit the text field being edited, committing it on enter.
+    pub fn input(&mut self, event: KeyEvent) {
+        let Some(editor) = self.editor.as_mut() else {
+            return;
+        };
+
+        match event.code {
+            KeyCode::Esc => self.editor = None,
+            KeyCode::Enter => {
+                let text = editor.lines().join("").trim().to_string();
+                self.editor = None;
+                let field = self.field();
+                if let Some(value) = self.value_mut(field) {
+                    *value = text;
+                }
+            }
+            _ => {
+                editor.input(event);
+            }
+        }
+    }
+
+    /// The named server, if it is valid and the name is free in `config`.
+    fn submit(&mut self, config: &McpConfig) -> Option<(String, McpServerConfig)> {
+        self.errors.clear();
+        if self.name.is_empty() {
+            self.errors.push("the name is empty".to_string());
+        } else if config.mcp_servers.contains_key(&self.name) {
+            self.errors.push(format!("a server is already named \"{}\"", self.name));
+        }
+
+        let mut server = McpServerConfig {
+            transport: self.transport,
+            enabled: true,
+            ..Default::default()
+        };
+        match self.transport {
+            Transport::Stdio => {
+                server.command = (!self.command.is_empty()).then(|| self.command.clone());
+                server.args = self.args.split_whitespace().map(str::to_string).collect();
+                for pair in self.env.split_whitespace() {
+                    match pair.split_once('=') {
+                        Some((key, value)) if !key.is_empty() => {
+                            server.env.insert(key.to_string(), value.to_string());
+                        }
+                        _ => self.errors.push(format!("\"{}\" is not KEY=value", pair)),
+                    }
+                }
+            }
+            _ => server.url = (!self.url.is_empty()).then(|| self.url.clone()),
+        }
+        self.errors.extend(server.validate());
+
+        self.errors.is_empty().then(|| (self.name.clone(), server))
+    }
+}
+
+pub struct Mcp;
+
+impl StatefulWidget for Mcp {
+    type State = McpState;
+
+    fn render(self, area: Rect, buf: &mut Buffer, state: &mut Self::State) {
+        let area = centered_rect(area, 90, 80);
+        Clear.render(area, buf);
+
+        let status = match &state.status {
+            FormStatus::Loading => " loading…".dark_gray(),
+            FormStatus::Ready => "".into(),
+            FormStatus::Saving => " applying…".dark_gray(),
+            FormStatus::Saved => " applied".green(),
+            FormStatus::Failed(_) => " failed".red(),
+        };
+        let title = if state.form.is_some() { "Add MCP server" } else { "MCP servers" };
+        let block = Block::default()
+            .borders(Borders::ALL)
+            .border_type(BorderType::Rounded)
+            .border_style(Style::default().fg(Color::Blue))
+            .title(Line::from(vec![title.into(), status]));
+        let inner = block.inner(area);
+        block.render(area, buf);
+
+        let [inner, error] = Layout::vertical([
+            Constraint::Min(0),
+            Constraint::Length(if matches!(state.status, FormStatus::Failed(_)) { 2 } else { 0 }),
+        ])
+        .areas(inner);
+        if let FormStatus::Failed(message) = &state.status {
+            Paragraph::new(message.as_str().red())
+                .wrap(Wrap { trim: true })
+                .render(error, buf);
+        }
+
+        if let Some(form) = &state.form {
+            render_form(form, inner, buf);
+            return;
+        }
+
+        let Some(config) = &state.config else {
+            if state.status == FormStatus::Loading {
+                Line::from("loading the mcp servers from the host…".dark_gray()).render(inner, buf);
+            }
+            return;
+        };
+        if config.mcp_servers.is_empty() {
+            Line::from("no mcp servers, press a to add one".dark_gray()).render(inner, buf);
+            return;
+        }
+
+        let [servers, details] = Layout::horizontal([Constraint::Length(32), Constraint::Min(0)])
+            .spacing(1)
+            .areas(inner);
+
+        let items = config.mcp_servers.iter().enumerate().map(|(i, (name, server))| {
+            let (dot, _) = server_status(state, name, server);
+            let mut line = Line::from(vec![dot, " ".into(), name.as_str().into()]);
+            if i == state.selected {
+                let style = match state.focus {
+                    Focus::Servers => Style::default().bg(Color::DarkGray),
+                    Focus::Tools => Style::default().add_modifier(Modifier::BOLD),
+                };
+                line = line.style(style);
+            }
+            ListItem::new(line)
+        });
+        let mut list = ListState::default().with_selected(Some(state.selected));
+        StatefulWidget::render(List::new(items), servers, buf, &mut list);
+
+        let Some((name, server)) = state.server() else {
+            return;
+        };
+        let (_, health) = server_status(state, name, server);
+        let target = match server.transport {
+            Transport::Stdio => {
+                let command = std::iter::once(server.command.as_deref().unwrap_or("-"))
+                    .chain(server.args.iter().map(String::as_str))
+                    .collect::<Vec<_>>()
+                    .join(" ");
+                field("command", command.into())
+            }
+            _ => field("url", server.url.clone().unwrap_or_else(|| "-".to_string()).into()),
+        };
+        let description = state.status_of(name).map(|status| status.description.clone()).unwrap_or_default();
+
+        let mut lines = vec![
+            Line::from(name.as_str().bold()),
+            field("transport", server.transport.name().into()),
+            target,
+            field("enabled", if server.enabled { "[x]".green() } else { "[ ]".into() }),
+            field("status", health),
+        ];
+        if !description.is_empty() {
+            lines.push(Line::from(description.dark_gray()));
+        }
+        lines.push(Line::default());
+        lines.push(Line::from("Tools".bold()));
+
+        let [header, tools_area] = Layout::vertical([Constraint::Length(lines.len() as u16), Constraint::Min(0)])
+            .areas(details);
+        Paragraph::new(lines).render(header, buf);
+
+        let tools = state.tools();
+        if tools.is_empty() {
+            Line::from("none".dark_gray()).render(tools_area, buf);
+            return;
+        }
+        let items = tools.into_iter().map(|tool| {
+            let check = if tool.enabled { "[x] ".green() } else { "[ ] ".into() };
+            ListItem::new(Line::from(vec![
+                check,
+                tool.name.into(),
+                format!("  {}", tool.description.lines().next().unwrap_or_default()).dark_gray(),
+            ]))
+        });
+        state.tools_list.select((state.focus == Focus::Tools).then_some(state.tool));
+        let list = List::new(items).highlight_style(Style::default().bg(Color::DarkGray));
+        StatefulWidget::render(list, tools_area, buf, &mut state.tools_list);
+    }
+}
+
+/// A colored dot and a description of how the server is doing.
+fn server_status(state: &McpState, name: &str, server: &McpServerConfig) -> (Span<'static>, Span<'static>) {
+    if !server.enabled {
+        return ("●".dark_gray(), "disabled".dark_gray());
+    }
+
+    match &state.servers {
+        None => ("●".dark_gray(), "…".dark_gray()),
+        Some(Err(_)) => ("●".yellow(), "unknown, the host did not list its tools".yellow()),
+        Some(Ok(_)) => match state.status_of(name) {
+            Some(McpServerStatus { error: Some(error), .. }) => ("●".red(), format!("error: {}", error).red()),
+            Some(status) => ("●".green(), format!("running, {} tools", status.tools.len()).green()),
+            None => ("●".yellow(), "not running".yellow()),
+        },
+    }
+}
+
+fn field<'a>(label: &str, value: Span<'a>) -> Line<'a> {
+    Line::from(vec![format!("{:<11}", label).dark_gray(), value])
+}
+
+fn render_form(form: &ServerForm, area: Rect, buf: &mut Buffer) {
+    let mut lines = vec![];
+    for (i, field) in form.fields().iter().enumerate() {
+        let value: Span = match field {
+            FormField::Name => form.name.clone().into(),
+            FormField::Transport => format!("< {} >", form.transport.name()).into(),
+            FormField::Command => form.command.clone().into(),
+            FormField::Args => form.args.clone().into(),
+            FormField::Env => form.env.clone().into(),
+            FormField::Url => form.url.clone().into(),
+        };
+        let mut line = Line::from(vec![
+            Span::raw(format!("{:<width$}", field.label(), width = LABEL_WIDTH as usize)),
+            value,
+        ]);
+        if i == form.cursor {
+            line = line.style(Style::default().add_modifier(Modifier::REVERSED));
+        }
+        lines.push(line);
+    }
+
+    lines.push(Line::default());
+    let hint = match form.transport {
+        Transport::Stdio => "arguments are separated by spaces, the environment is KEY=value pairs",
+        _ => "the url of the server's event stream",
+    };
+    lines.push(Line::from(hint.dark_gray()));
+    lines.push(Line::from("[enter] edit | [h, l] transport | [s] add | [esc] cancel".dark_gray()));
+    for error in &form.errors {
+        lines.push(Line::from(format!("! {}", error).red()));
+    }
+    Paragraph::new(lines).render(area, buf);
+
+    if let Some(editor) = &form.editor
+        && area.width > LABEL_WIDTH
+        && (form.cursor as u16) < area.height
+    {
+        let field = Rect {
+            x: area.x + LABEL_WIDTH,
+            y: area.y + form.cursor as u16,
+            width: area.width - LABEL_WIDTH,
+            height: 1,
+        };
+        Clear.render(field, buf);
+        editor.render(field, buf);
+    }
+}
@@ -1,21 +1,38 @@
 use ratatui::{
     buffer::Buffer,
     layout::Rect,
-    style::{Color, Style, Stylize},
-    text::Line,
+    style::{Color, Modifier, Style, Stylize},
+    text::{Line, Span},
     widgets::{
         Block,
         BorderType,
         Borders,
         Paragraph,
+        Scrollbar,
+        ScrollbarOrientation,
+        ScrollbarState,
         StatefulWidget,
         StatefulWidgetRef,
         Widget,
     },
 };
 
+use std::hash::{DefaultHasher, Hash, Hasher};
+
+use unicode_width::UnicodeWidthStr;
+
 use crate::message;
 
+use super::{
+    markdown::{self, Markdown, RenderOptions},
+    search::{Search, SearchOptions},
+};
+
+const SCROLL_X_STEP: u16 = 4;
+
+const TOOL_BORDER: &str = "│ ";
+const TOOL_INDENT: &str = "│   ";
+
 const ANIMATION_CHAR_TOP: char = '>';
 const ANIMATION_CHAR_BOTTOM: char = '<';
 const ANIMATION_CHAR_LEFT: char = 'A';
@@ -44,88 +61,867 @@ impl Default for MessageAnimation {
     }
 }
 
+/// A position in the transcript, as the turn and the line inside it.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+struct Anchor {
+    turn: usize,
+    line: usize,
+}
+
+/// A cell of the transcript, as its line and column.
+type Point = (Anchor, u16);
+
+/// Text found by a search, its end column excluded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+struct Match {
+    at: Anchor,
+    start: u16,
+    end: u16,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum SearchJump {
+    /// The first match from where the search began.
+    First,
+    /// The match after the current one, going the other way if `reverse`.
+    Next { reverse: bool },
+}
+
+/// Where a transcript is scrolled to, kept while its chat is not on screen.
+#[derive(Debug, Clone, Copy)]
+pub struct TranscriptView {
+    top: Anchor,
+    /// Stay at the bottom as the transcript grows.
+    follow: bool,
+}
+
+impl Default for TranscriptView {
+    fn default() -> Self {
+        Self { top: Anchor::default(), follow: true }
+    }
+}
+
+/// One turn of the transcript as it should be shown.
+#[derive(Debug, Clone, Copy)]
+pub struct Turn<'a> {
+    pub frame: &'a message::MessageFrame,
+    /// The generation shown and how many the turn has, when more than one.
+    pub generation: Option<(usize, usize)>,
+}
+
+/// How turns are laid out, a change invalidates every layout.
+#[derive(Debug, Clone, Copy, Hash)]
+struct LayoutOptions {
+    width: u16,
+    scroll_x: u16,
+    expand_tools: bool,
+}
+
+/// The rendered lines of one turn, kept until the turn or the options change.
+#[derive(Debug, Clone, Default)]
+struct TurnLayout {
+    key: Option<u64>,
+    /// A guess at the height while the turn was never laid out.
+    estimate: Option<(u64, usize)>,
+    buf: Buffer,
+    height: usize,
+    code_width: usize,
+    markdown: Vec<Markdown>,
+    /// Where the search found something, as the line and the columns.
+    matches: Vec<(usize, u16, u16)>,
+    /// The search and the layout the matches were found for.
+    matches_key: Option<(u64, u64)>,
+}
+
+impl TurnLayout {
+    /// The height of the turn, guessed from its text if it is not laid out.
+    fn estimate(&mut self, turn: &Turn, options: LayoutOptions) -> usize {
+        let key = layout_key(turn, options);
+        if self.key == Some(key) {
+            return self.height;
+        }
+        if let Some((estimated, height)) = self.estimate
+            && estimated == key
+        {
+            return height;
+        }
+
+        let frame = turn.frame;
+        let width = (options.width as usize).max(1);
+        let wrapped = |text: &str| text.lines().map(|line| line.width().div_ceil(width).max(1)).sum::<usize>();
+        let height = if frame.is_empty() {
+            0
+        } else {
+            // the headers, the blank lines around them and what is below the ai text
+            4 + wrapped(&frame.user.body.content)
+                + wrapped(&frame.ai.body.content)
+                + frame.tools.len()
+                + frame.interrupted as usize
+                + frame.error.as_ref().map_or(0, |_| 3)
+        };
+        self.estimate = Some((key, height));
+        height
+    }
+
+    fn update(&mut self, turn: &Turn, options: LayoutOptions) {
+        let key = layout_key(turn, options);
+        if self.key == Some(key) {
+            return;
+        }
+        self.key = Some(key);
+
+        let frame = turn.frame;
+        if frame.is_empty() {
+            self.buf = Buffer::default();
+            self.height = 0;
+            self.code_width = 0;
+            return;
+        }
+
+        let (user_message, _) = frame.split_ref();
+        let width = options.width as usize;
+        let opt = textwrap::Options::new(width.max(1)).word_separator(textwrap::WordSeparator::AsciiSpace);
+
+        let mut text: Vec<Line> = Vec::new();
+        text.push(Line::from("You >".blue()));
+        text.extend(textwrap::wrap(&user_message.body.content, &opt).into_iter().map(Line::from));
+        text.push("".into());
+        text.push(match turn.generation {
+            Some((shown, total)) => Line::from(vec!["AI >".green(), format!(" ({}/{})", shown, total).dark_gray()]),
+            None => Line::from("AI >".green()),
+        });
+
+        // split the ai content at the points where tools were called
+        let segments = segments(frame);
+        let mut first_block = 0;
+        self.code_width = 0;
+        for (i, segment) in segments.iter().enumerate() {
+            first_block = self.push_markdown(&mut text, i, segment, first_block, options);
+            if let Some(tool) = frame.tools.get(i) {
+                push_tool(&mut text, tool, width, options.expand_tools);
+            }
+        }
+
+        if frame.interrupted {
+            text.push(Line::from("[interrupted]".red().italic()));
+        }
+
+        if let Some(error) = &frame.error {
+            text.push("".into());
+            text.extend(textwrap::wrap(&format!("✗ {}", error), &opt).into_iter().map(|line| Line::from(line.into_owned().red())));
+            text.push(Line::from("press r to retry".dark_gray().italic()));
+        }
+
+        // keep turns apart
+        text.push("".into());
+
+        self.height = text.len();
+        let rect = Rect::new(0, 0, options.width, self.height.min(u16::MAX as usize) as u16);
+        self.buf = Buffer::empty(rect);
+        Paragraph::new(text).render(rect, &mut self.buf);
+    }
+
+    /// The matches of `search` in the laid out turn.
+    fn matches(&mut self, search: &Search) -> &[(usize, u16, u16)] {
+        let key = (search.id, self.key.unwrap_or_default());
+        if self.matches_key != Some(key) {
+            let buf = &self.buf;
+            self.matches = (0..self.height)
+                .flat_map(|line| search.find_in_row(buf, line as u16).into_iter().map(move |(start, end)| (line, start, end)))
+                .collect();
+            self.matches_key = Some(key);
+        }
+        &self.matches
+    }
+
+    /// Render one segment of the ai content, returning the number of code
+    /// blocks up to its end.
+    fn push_markdown(&mut self, text: &mut Vec<Line>, segment: usize, content: &str, first_block: usize, options: LayoutOptions) -> usize {
+        if content.trim().is_empty() {
+            return first_block;
+        }
+
+        if self.markdown.len() <= segment {
+            self.markdown.resize_with(segment + 1, Markdown::default);
+        }
+
+        let options = RenderOptions {
+            width: options.width as usize,
+            scroll_x: options.scroll_x as usize,
+            first_block,
+        };
+        let markdown = &mut self.markdown[segment];
+        text.extend(markdown.render(content, options));
+        self.code_width = self.code_width.max(markdown.code_width());
+        first_block + markdown.code_blocks()
+    }
+}
+
+/// Identify what a turn looks like, without hashing the whole ai content of
+/// frames that are done.
+fn layout_key(turn: &Turn, options: LayoutOptions) -> u64 {
+    let frame = turn.frame;
+    let mut hasher = DefaultHasher::new();
+    std::ptr::hash(frame, &mut hasher);
+    frame.user.body.content.hash(&mut hasher);
+    frame.ai.body.message_id.hash(&mut hasher);
+    frame.ai.body.content.len().hash(&mut hasher);
+    frame.tools.len().hash(&mut hasher);
+    frame.tools.iter().filter(|tool| tool.result.is_some()).count().hash(&mut hasher);
+    frame.interrupted.hash(&mut hasher);
+    frame.error.hash(&mut hasher);
+    turn.generation.hash(&mut hasher);
+    options.hash(&mut hasher);
+    hasher.finish()
+}
+
+fn push_tool<'a>(text: &mut Vec<Line<'a>>, tool: &'a message::ToolInvocation, width: usize, expand_tools: bool) {
+    let border = Style::default().fg(Color::Yellow);
+    let (marker, status) = match (&tool.result, expand_tools) {
+        (_, true) => ("▾", ""),
+        (Some(_), false) => ("▸", ""),
+        (None, false) => ("▸", " (running)"),
+    };
+
+    text.push(Line::from(vec![
+        format!("{} tool ", marker).fg(Color::Yellow),
+        tool.name.as_str().fg(Color::Yellow).bold(),
+        status.dark_gray(),
+    ]));
+
+    if !expand_tools {
+        return;
+    }
+
+    let opt = textwrap::Options::new(width.saturating_sub(TOOL_INDENT.chars().count()).max(1));
+    let mut section = |title: &'static str, body: &'a str| {
+        text.push(Line::from(vec![Span::styled(TOOL_BORDER, border), title.dark_gray()]));
+        for line in body.lines().flat_map(|line| textwrap::wrap(line, &opt)) {
+            text.push(Line::from(vec![Span::styled(TOOL_INDENT, border), Span::styled(line, Style::default().fg(Color::Gray))]));
+        }
+    };
+
+    section("arguments", &tool.arguments);
+    match &tool.result {
+        Some(result) => section("result", result),
+        None => section("result", "running..."),
+    }
+}
+
+/// Every turn of a chat as one scrollable document. Only the turns that come
+/// into view are laid out.
 #[derive(Debug, Clone)]
 pub struct MessageState {
-    buf: Buffer,
-    scroll_y: u16,
+    scroll_x: u16,
+    code_width: usize,
+    /// The area inside the border.
     viewport: Rect,
     animation: MessageAnimation,
+    expand_tools: bool,
+    layouts: Vec<TurnLayout>,
+    view: TranscriptView,
+    /// Lines to scroll on the next render, negative is up.
+    pending: isize,
+    /// Paragraphs to move on the next render, negative is up.
+    paragraphs: isize,
+    current: usize,
+    /// Line at the top and length of the transcript, for the scrollbar.
+    position: usize,
+    total: usize,
+    /// Text picked with the mouse, from where the drag started to where it is.
+    selection: Option<(Point, Point)>,
+    search: Option<Search>,
+    /// Where the transcript was when the search prompt opened.
+    search_origin: Option<TranscriptView>,
+    /// Find a match on the next render.
+    search_jump: Option<SearchJump>,
+    current_match: Option<Match>,
+    /// The last jump found nothing.
+    search_failed: bool,
 }
 
 impl MessageState {
     pub fn new(viewport: Rect) -> Self {
-        Self {
-            buf: Buffer::default(),
-            scroll_y: 0,
-            viewport,
+        let mut state = Self {
+            scroll_x: 0,
+            code_width: 0,
+            viewport: Rect::default(),
             animation: MessageAnimation::default(),
-        }
+            expand_tools: false,
+            layouts: vec![],
+            view: TranscriptView::default(),
+            pending: 0,
+            paragraphs: 0,
+            current: 0,
+            position: 0,
+            total: 0,
+            selection: None,
+            search: None,
+            search_origin: None,
+            search_jump: None,
+            current_match: None,
+            search_failed: false,
+        };
+        state.set_viewport(viewport);
+        state
     }
 
+    /// Go back to the bottom of the transcript, following it.
     pub fn reset(&mut self) {
-        self.scroll_y = 0;
-        self.buf.reset();
+        self.set_view(TranscriptView::default());
+    }
+
+    #[inline]
+    pub fn view(&self) -> TranscriptView {
+        self.view
     }
 
-    pub fn set_viewport(&mut self, viewport: Rect) {
-        self.viewport = viewport;
+    pub fn set_view(&mut self, view: TranscriptView) {
+        self.view = view;
+        self.pending = 0;
+        self.paragraphs = 0;
+        self.scroll_x = 0;
     }
 
-    pub fn scroll_up(&mut self) {
-        self.scroll_y = self.scroll_y.saturating_sub(1);
+    /// The turn the reader is at: the one at the top of the viewport, or the
+    /// last one when following the transcript.
+    #[inline]
+    pub fn current_turn(&self) -> usize {
+        self.current
     }
 
-    pub fn scroll_down(&mut self) {
-        let scroll_y = self.scroll_y.saturating_add(1);
-        if scroll_y + self.viewport.height <= self.buf.area().height {
-            self.scroll_y = scroll_y;
+    /// Set the chat area, including its border.
+    pub fn set_viewport(&mut self, area: Rect) {
+        self.viewport = Rect::new(
+            area.x.saturating_add(1),
+            area.y.saturating_add(1),
+            area.width.saturating_sub(2),
+            area.height.saturating_sub(2),
+        );
+    }
+
+    pub fn scroll_up(&mut self, lines: usize) {
+        self.view.follow = false;
+        self.pending -= lines as isize;
+    }
+
+    pub fn scroll_down(&mut self, lines: usize) {
+        self.pending += lines as isize;
+    }
+
+    #[inline]
+    pub fn page(&self) -> usize {
+        (self.viewport.height as usize).max(1)
+    }
+
+    #[inline]
+    pub fn half_page(&self) -> usize {
+        (self.page() / 2).max(1)
+    }
+
+    pub fn scroll_top(&mut self) {
+        self.view = TranscriptView { top: Anchor::default(), follow: false };
+        self.pending = 0;
+        self.paragraphs = 0;
+    }
+
+    pub fn scroll_bottom(&mut self) {
+        self.view.follow = true;
+        self.pending = 0;
+        self.paragraphs = 0;
+    }
+
+    /// Put the start of the `n`th next paragraph at the top, or of the
+    /// previous ones when negative.
+    pub fn jump_paragraph(&mut self, n: isize) {
+        if n < 0 {
+            self.view.follow = false;
         }
+        self.paragraphs += n;
     }
 
-    pub fn draw(&mut self, area: Rect, buf: &mut Buffer) {
-        let Rect { x, y, width, height } = area;
-        for h in 0..height {
-            for w in 0..width {
-                let Some(_cell) = self.buf.cell((w, h + self.scroll_y)) else {
-                    continue;
-                };
-                if let Some(cell) = buf.cell_mut((x + w, y + h)) {
-                    *cell = _cell.clone();
+    /// Scroll code blocks left, the rest of the text always wraps.
+    pub fn scroll_left(&mut self) {
+        self.scroll_x = self.scroll_x.saturating_sub(SCROLL_X_STEP);
+    }
+
+    pub fn scroll_right(&mut self) {
+        let scroll_x = self.scroll_x.saturating_add(SCROLL_X_STEP);
+        if (scroll_x as usize) < self.code_width {
+            self.scroll_x = scroll_x;
+        }
+    }
+
+    /// Put the start of the turn before the current one at the top, or the
+    /// start of the current one if it is scrolled into.
+    pub fn previous_turn(&mut self) {
+        let top = if self.view.follow { Anchor { turn: self.current, line: 0 } } else { self.view.top };
+        let turn = if top.line > 0 { top.turn } else { top.turn.saturating_sub(1) };
+        self.view = TranscriptView { top: Anchor { turn, line: 0 }, follow: false };
+        self.pending = 0;
+        self.paragraphs = 0;
+    }
+
+    /// Put the start of the turn after the current one at the top.
+    pub fn next_turn(&mut self) {
+        if self.view.follow {
+            return;
+        }
+        self.view.top = Anchor { turn: self.view.top.turn + 1, line: 0 };
+        self.pending = 0;
+        self.paragraphs = 0;
+    }
+
+    /// The transcript cell at the screen position, if it is in view.
+    fn point(&self, x: u16, y: u16) -> Option<Point> {
+        let viewport = self.viewport;
+        if !viewport.contains((x, y).into()) {
+            return None;
+        }
+
+        let Anchor { mut turn, mut line } = self.view.top;
+        line += (y - viewport.y) as usize;
+        while turn < self.layouts.len() && line >= self.layouts[turn].height {
+            line -= self.layouts[turn].height;
+            turn += 1;
+        }
+        (turn < self.layouts.len()).then_some((Anchor { turn, line }, x - viewport.x))
+    }
+
+    /// Start selecting text at the screen position.
+    pub fn select_start(&mut self, x: u16, y: u16) {
+        self.selection = self.point(x, y).map(|point| (point, point));
+    }
+
+    /// Move the end of the selection to the screen position, which is kept
+    /// inside the transcript, scrolling when dragged past its edges.
+    pub fn select_to(&mut self, x: u16, y: u16) {
+        let Some((start, _)) = self.selection else {
+            return;
+        };
+
+        let viewport = self.viewport;
+        if y < viewport.top() {
+            self.scroll_up(1);
+        } else if y >= viewport.bottom() {
+            self.scroll_down(1);
+        }
+
+        let x = x.clamp(viewport.left(), viewport.right().saturating_sub(1));
+        let y = y.clamp(viewport.top(), viewport.bottom().saturating_sub(1));
+        if let Some(end) = self.point(x, y) {
+            self.selection = Some((start, end));
+        }
+    }
+
+    pub fn clear_selection(&mut self) {
+        self.selection = None;
+    }
+
+    /// The selected text, one line per transcript line without the padding
+    /// on the right.
+    pub fn selected_text(&self) -> Option<String> {
+        let (start, end) = self.ordered_selection()?;
+        if start == end {
+            return None;
+        }
+
+        let mut lines = vec![];
+        let Anchor { mut turn, mut line } = start.0;
+        while (Anchor { turn, line }) <= end.0 {
+            let Some(layout) = self.layouts.get(turn) else {
+                break;
+            };
+            if line >= layout.height {
+                turn += 1;
+                line = 0;
+                continue;
+            }
+
+            let here = Anchor { turn, line };
+            let from = if here == start.0 { start.1 } else { 0 };
+            let to = if here == end.0 { end.1 } else { u16::MAX };
+            let mut text = String::new();
+            let mut x = from;
+            while x <= to {
+                let Some(cell) = layout.buf.cell((x, line as u16)) else {
+                    break;
                 };
+                text.push_str(cell.symbol());
+                // the cells a wide character covers hold nothing of their own
+                x = x.saturating_add(cell.symbol().width().max(1) as u16);
             }
+            lines.push(text.trim_end().to_string());
+            line += 1;
+        }
+
+        Some(lines.join("\n"))
+    }
+
+    #[inline]
+    fn ordered_selection(&self) -> Option<(Point, Point)> {
+        let (a, b) = self.selection?;
+        Some(if a <= b { (a, b) } else { (b, a) })
+    }
+
+    /// Open a search from where the transcript is now.
+    pub fn begin_search(&mut self) {
+        self.search_origin = Some(self.view);
+    }
+
+    /// Search for `query` as it is typed, from where the search began.
+    pub fn set_search(&mut self, query: &str, options: SearchOptions) -> Result<(), regex::Error> {
+        if let Some(origin) = self.search_origin {
+            self.set_view(origin);
+        }
+        self.current_match = None;
+        self.search_failed = false;
+        self.search = None;
+        if query.is_empty() {
+            return Ok(());
+        }
+
+        self.search = Some(Search::new(query, options)?);
+        self.search_jump = Some(SearchJump::First);
+        Ok(())
+    }
+
+    /// Close the search prompt, keeping the matches.
+    pub fn confirm_search(&mut self) {
+        self.search_origin = None;
+    }
+
+    /// Drop the search and go back to where it began.
+    pub fn cancel_search(&mut self) {
+        if let Some(origin) = self.search_origin.take() {
+            self.set_view(origin);
+        }
+        self.clear_search();
+    }
+
+    pub fn clear_search(&mut self) {
+        self.search = None;
+        self.search_jump = None;
+        self.current_match = None;
+        self.search_failed = false;
+    }
+
+    /// Go to the next match in the direction of the search, or the other way
+    /// if `reverse`.
+    pub fn search_next(&mut self, reverse: bool) {
+        if self.search.is_some() {
+            self.search_jump = Some(SearchJump::Next { reverse });
         }
     }
 
-    pub fn pre_render(&mut self, user_message: &message::UserMessage, ai_message: &message::AIMessage) {
-        if user_message.body.content.is_empty() && ai_message.body.content.is_empty() {
+    #[inline]
+    pub fn search_failed(&self) -> bool {
+        self.search_failed
+    }
+
+    fn jump_to_match(&mut self, turns: &[Turn], jump: SearchJump) {
+        let Some(search) = self.search.clone() else {
             return;
+        };
+
+        let forward = match jump {
+            SearchJump::First => !search.backward,
+            SearchJump::Next { reverse } => search.backward == reverse,
+        };
+        let top = if self.view.follow { self.bottom(turns) } else { self.view.top };
+        let end = self.view_end(turns, top);
+        let (from, inclusive) = match (jump, self.current_match) {
+            (SearchJump::Next { .. }, Some(current)) => (current, false),
+            // a new search starts at the screen, from the side it looks towards
+            _ if forward => (Match { at: top, start: 0, end: 0 }, true),
+            _ => (Match { at: end, start: u16::MAX, end: u16::MAX }, true),
+        };
+
+        let found = self.find_match(turns, &search, from, forward, inclusive);
+        self.search_failed = found.is_none();
+        self.current_match = found;
+        if let Some(found) = found
+            && !(top <= found.at && found.at <= end)
+        {
+            // show the match a third of the way down
+            self.view = TranscriptView { top: found.at, follow: false };
+            self.pending = -((self.page() / 3) as isize);
         }
+    }
 
-        let opt = textwrap::Options::new(self.viewport.width as usize).word_separator(textwrap::WordSeparator::AsciiSpace);
-        let user_lines = textwrap::wrap(&user_message.body.content, &opt);
-        let ai_lines = textwrap::wrap(&ai_message.body.content, opt);
-        // +2 for the `you >` and `ai >` lines
-        // +1 for the space
-        let total_lines = user_lines.len() + ai_lines.len() + 3;
+    /// The last line in view when `top` is at the top.
+    fn view_end(&mut self, turns: &[Turn], top: Anchor) -> Anchor {
+        let mut end = top;
+        for _ in 1..self.page() {
+            match self.next_line(turns, end) {
+                Some(next) => end = next,
+                None => break,
+            }
+        }
+        end
+    }
 
-        let rect = if total_lines >= self.viewport.height as usize {
-            Rect::new(self.viewport.x, self.viewport.y, self.viewport.width, total_lines as u16)
-        } else {
-            self.viewport
+    /// The nearest match past `from`, wrapping around the transcript.
+    fn find_match(&mut self, turns: &[Turn], search: &Search, from: Match, forward: bool, inclusive: bool) -> Option<Match> {
+        let len = turns.len();
+        let past = |m: &Match| match (forward, inclusive) {
+            (true, true) => *m >= from,
+            (true, false) => *m > from,
+            (false, true) => *m <= from,
+            (false, false) => *m < from,
         };
 
-        self.buf.resize(rect);
+        // the turn of `from` comes twice, for the matches on each side of it
+        for step in 0..=len {
+            let turn = if forward { (from.at.turn + step) % len } else { (from.at.turn + 2 * len - step) % len };
+            self.height(turns, turn);
+            let matches: Vec<Match> = self.layouts[turn].matches(search).iter()
+                .map(|&(line, start, end)| Match { at: Anchor { turn, line }, start, end })
+                .filter(|m| step > 0 || past(m))
+                .collect();
 
-        let mut text = Vec::with_capacity(total_lines);
-        text.push(Line::from("You >".blue()));
-        text.extend(user_lines.iter().map(|line| line.as_ref().into()));
-        text.push("".into());
-        text.push(Line::from("AI >".green()));
-        text.extend(ai_lines.iter().map(|line| line.as_ref().into()));
+            let found = if forward { matches.first() } else { matches.last() };
+            if found.is_some() {
+                return found.copied();
+            }
+        }
+        None
+    }
 
-        Paragraph::new(text).render(rect, &mut self.buf);
+    pub fn toggle_tools(&mut self) {
+        self.expand_tools = !self.expand_tools;
+    }
+
+    #[inline]
+    fn options(&self) -> LayoutOptions {
+        LayoutOptions {
+            width: self.viewport.width,
+            scroll_x: self.scroll_x,
+            expand_tools: self.expand_tools,
+        }
+    }
+
+    fn height(&mut self, turns: &[Turn], turn: usize) -> usize {
+        let options = self.options();
+        let layout = &mut self.layouts[turn];
+        layout.update(&turns[turn], options);
+        layout.height
     }
 
+    /// The top that shows the end of the transcript at the bottom.
+    fn bottom(&mut self, turns: &[Turn]) -> Anchor {
+        let mut room = self.viewport.height as usize;
+        for turn in (0..turns.len()).rev() {
+            let height = self.height(turns, turn);
+            if height >= room {
+                return Anchor { turn, line: height - room };
+            }
+            room -= height;
+        }
+        Anchor::default()
+    }
+
+    /// Lay out the turns in view, applying the scrolling asked for since the
+    /// last render.
+    pub fn pre_render(&mut self, turns: &[Turn]) {
+        self.layouts.resize_with(turns.len(), TurnLayout::default);
+        if turns.is_empty() {
+            self.view = TranscriptView::default();
+            self.current = 0;
+            self.code_width = 0;
+            self.position = 0;
+            self.total = 0;
+            self.pending = 0;
+            return;
+        }
+
+        if self.view.top.turn >= turns.len() {
+            self.view.top = Anchor { turn: turns.len() - 1, line: 0 };
+        }
+        if let Some(jump) = self.search_jump.take() {
+            self.jump_to_match(turns, jump);
+        }
+
+        let pending = std::mem::take(&mut self.pending);
+        let mut top = self.view.top;
+
+        if pending >= 0 {
+            top.line += pending as usize;
+        } else {
+            let mut up = pending.unsigned_abs();
+            while up > top.line {
+                if top.turn == 0 {
+                    up = top.line;
+                    break;
+                }
+                up -= top.line;
+                top.turn -= 1;
+                top.line = self.height(turns, top.turn);
+            }
+            top.line -= up;
+        }
+
+        // moving down, or a resize, may leave the line past its turn
+        while top.turn + 1 < turns.len() && top.line >= self.height(turns, top.turn) {
+            top.line -= self.height(turns, top.turn);
+            top.turn += 1;
+        }
+
+        let paragraphs = std::mem::take(&mut self.paragraphs);
+        for _ in 0..paragraphs.unsigned_abs() {
+            top = if paragraphs > 0 { self.next_paragraph(turns, top) } else { self.previous_paragraph(turns, top) };
+        }
+
+        // never leave empty space below the end, reaching it follows again
+        let bottom = self.bottom(turns);
+        if self.view.follow || top >= bottom {
+            self.view = TranscriptView { top: bottom, follow: true };
+        } else {
+            self.view.top = top;
+        }
+
+        // lay out what is in view
+        let mut room = self.viewport.height as usize;
+        let mut line = self.view.top.line;
+        self.code_width = 0;
+        for turn in self.view.top.turn..turns.len() {
+            if room == 0 {
+                break;
+            }
+            let height = self.height(turns, turn);
+            self.code_width = self.code_width.max(self.layouts[turn].code_width);
+            if let Some(search) = &self.search {
+                self.layouts[turn].matches(search);
+            }
+            room = room.saturating_sub(height.saturating_sub(line));
+            line = 0;
+        }
+
+        self.current = if self.view.follow { turns.len() - 1 } else { self.view.top.turn };
+
+        // turns never laid out count with a guess at their height
+        let options = self.options();
+        self.position = self.layouts[..self.view.top.turn].iter_mut()
+            .zip(turns)
+            .map(|(layout, turn)| layout.estimate(turn, options))
+            .sum::<usize>() + self.view.top.line;
+        self.total = self.layouts.iter_mut()
+            .zip(turns)
+            .map(|(layout, turn)| layout.estimate(turn, options))
+            .sum();
+    }
+
+    /// The line after `at`, skipping empty turns.
+    fn next_line(&mut self, turns: &[Turn], at: Anchor) -> Option<Anchor> {
+        if at.line + 1 < self.height(turns, at.turn) {
+            return Some(Anchor { line: at.line + 1, ..at });
+        }
+        (at.turn + 1..turns.len())
+            .find(|&turn| self.height(turns, turn) > 0)
+            .map(|turn| Anchor { turn, line: 0 })
+    }
+
+    /// The line before `at`, skipping empty turns.
+    fn previous_line(&mut self, turns: &[Turn], at: Anchor) -> Option<Anchor> {
+        if at.line > 0 {
+            return Some(Anchor { line: at.line - 1, ..at });
+        }
+        (0..at.turn).rev()
+            .map(|turn| (turn, self.height(turns, turn)))
+            .find(|&(_, height)| height > 0)
+            .map(|(turn, height)| Anchor { turn, line: height - 1 })
+    }
+
+    fn is_blank(&mut self, turns: &[Turn], at: Anchor) -> bool {
+        if at.line >= self.height(turns, at.turn) {
+            return true;
+        }
+        let buf = &self.layouts[at.turn].buf;
+        (0..buf.area.width).all(|x| buf.cell((x, at.line as u16)).is_none_or(|cell| cell.symbol().trim().is_empty()))
+    }
+
+    /// The first line of the paragraph after the one at `at`.
+    fn next_paragraph(&mut self, turns: &[Turn], mut at: Anchor) -> Anchor {
+        while !self.is_blank(turns, at) {
+            let Some(next) = self.next_line(turns, at) else {
+                return at;
+            };
+            at = next;
+        }
+        while self.is_blank(turns, at) {
+            let Some(next) = self.next_line(turns, at) else {
+                return at;
+            };
+            at = next;
+        }
+        at
+    }
+
+    /// The first line of the paragraph at `at`, or of the one before if `at`
+    /// already starts one.
+    fn previous_paragraph(&mut self, turns: &[Turn], at: Anchor) -> Anchor {
+        let Some(mut at) = self.previous_line(turns, at) else {
+            return at;
+        };
+        while self.is_blank(turns, at) {
+            let Some(previous) = self.previous_line(turns, at) else {
+                return at;
+            };
+            at = previous;
+        }
+        while let Some(previous) = self.previous_line(turns, at)
+            && !self.is_blank(turns, previous)
+        {
+            at = previous;
+        }
+        at
+    }
+
+    /// The line at the top of the viewport and the length of the transcript,
+    /// partly guessed.
+    #[inline]
+    pub fn scroll_position(&self) -> (usize, usize) {
+        (self.position, self.total)
+    }
+
+    pub fn draw(&mut self, area: Rect, buf: &mut Buffer) {
+        let selection = self.ordered_selection();
+        let Anchor { mut turn, mut line } = self.view.top;
+        for y in area.top()..area.bottom() {
+            while turn < self.layouts.len() && line >= self.layouts[turn].height {
+                turn += 1;
+                line = 0;
+            }
+            let Some(layout) = self.layouts.get(turn) else {
+                break;
+            };
+            let matches: Vec<Match> = match &self.search {
+                Some(search) if layout.matches_key.is_some_and(|(id, _)| id == search.id) => layout.matches.iter()
+                    .filter(|(l, _, _)| *l == line)
+                    .map(|&(line, start, end)| Match { at: Anchor { turn, line }, start, end })
+                    .collect(),
+                _ => vec![],
+            };
+
+            for (i, x) in (area.left()..area.right()).enumerate() {
+                let Some(cell) = layout.buf.cell((i as u16, line as u16)) else {
+                    break;
+                };
+                if let Some(target) = buf.cell_mut((x, y)) {
+                    *target = cell.clone();
+                    let point = (Anchor { turn, line }, i as u16);
+                    if let Some(found) = matches.iter().find(|m| m.start <= point.1 && point.1 < m.end) {
+                        let bg = if self.current_match == Some(*found) { Color::LightRed } else { Color::Yellow };
+                        target.set_style(Style::default().fg(Color::Black).bg(bg));
+                    }
+                    if selection.is_some_and(|(start, end)| start <= point && point <= end) {
+                        target.set_style(Style::default().add_modifier(Modifier::REVERSED));
+                    }
+                }
+            }
+            line += 1;
+        }
+    }
     // pub fn render_border_animation(&mut self, area: Rect, buf: &mut Buffer) {
     //     const CHAR_SIZE: u16 = 2;
     //
@@ -169,6 +965,29 @@ impl MessageState {
     // }
 }
 
+/// The ai content of a frame, cut at the points where tools were called.
+fn segments(frame: &message::MessageFrame) -> Vec<&str> {
+    let content = &frame.ai.body.content;
+    let mut segments = Vec::with_capacity(frame.tools.len() + 1);
+    let mut start = 0;
+    for tool in &frame.tools {
+        let end = tool.offset.clamp(start, content.len());
+        segments.push(&content[start..end]);
+        start = end;
+    }
+    segments.push(&content[start..]);
+    segments
+}
+
+/// The code blocks of a frame, numbered the same way they are rendered.
+pub fn code_blocks(frame: &message::MessageFrame) -> Vec<String> {
+    segments(frame)
+        .into_iter()
+        .filter(|segment| !segment.trim().is_empty())
+        .flat_map(markdown::code_blocks)
+        .collect()
+}
+
 pub struct Message {
     pub streaming: bool,
 }
@@ -204,7 +1023,23 @@ impl StatefulWidgetRef for Message {
     fn render_ref(&self, area:Rect, buf: &mut Buffer, state: &mut Self::State) {
         self.render_border(area, buf, self.streaming, state);
 
-        let area = Rect::new(area.x + 1, area.y + 1, area.width - 2, area.height - 2);
+        // on the right border, between the corners
+        let (position, total) = state.scroll_position();
+        let page = state.page();
+        if total > page {
+            let mut scrollbar = ScrollbarState::new(total - page + 1)
+                .position(position)
+                .viewport_content_length(page);
+            let track = Rect::new(area.x, area.y.saturating_add(1), area.width, area.height.saturating_sub(2));
+            Scrollbar::new(ScrollbarOrientation::VerticalRight)
+                .begin_symbol(None)
+                .end_symbol(None)
+                .track_symbol(None)
+                .thumb_style(Style::default().fg(if self.streaming { Color::Green } else { Color::White }))
+                .render(track, buf, &mut scrollbar);
+        }
+
+        let area = Rect::new(area.x + 1, area.y + 1, area.width.saturating_sub(2), area.height.saturating_sub(2));
         state.draw(area, buf);
     }
 }
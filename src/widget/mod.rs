@@ -1,2 +1,27 @@
+use ratatui::layout::{Constraint, Flex, Layout, Rect};
+
+pub mod chat_search;
+pub mod diagnostics;
+pub mod highlight;
+pub mod history;
+pub mod logs;
+pub mod markdown;
+pub mod mcp;
+pub mod model_form;
+pub mod model_switcher;
+pub mod search;
+pub mod sidebar;
 pub mod status_bar;
-pub mod message;
\ No newline at end of file
+pub mod message;
+
+/// Area for a popup taking the given percentage of `area`.
+pub fn centered_rect(area: Rect, percent_x: u16, percent_y: u16) -> Rect {
+    let [area] = Layout::vertical([Constraint::Percentage(percent_y)])
+        .flex(Flex::Center)
+        .areas(area);
+    let [area] = Layout::horizontal([Constraint::Percentage(percent_x)])
+        .flex(Flex::Center)
+        .areas(area);
+
+    area
+}
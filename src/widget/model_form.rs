@@ -0,0 +1,424 @@
+use crossterm::event::{KeyCode, KeyEvent};
+use ratatui::{
+    buffer::Buffer,
+    layout::Rect,
+    style::{Color, Modifier, Style, Stylize},
+    text::{Line, Span},
+    widgets::{
+        Block,
+        BorderType,
+        Borders,
+        Clear,
+        Paragraph,
+        StatefulWidget,
+        Widget,
+    },
+};
+use tui_textarea::TextArea;
+
+use crate::model_config::{ConfigError, ModelConfig, ProviderConfig, FAKE_PROVIDER, MODEL_PROVIDERS};
+
+use super::centered_rect;
+
+/// Columns taken by the field names.
+const LABEL_WIDTH: u16 = 32;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Row {
+    ActiveProvider,
+    EnableTools,
+    DisableSystemPrompt,
+    Provider(usize, ProviderField),
+    AddProvider,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ProviderField {
+    Name,
+    Kind,
+    Model,
+    ApiKey,
+    BaseUrl,
+}
+
+impl ProviderField {
+    const ALL: [ProviderField; 5] = [
+        ProviderField::Name,
+        ProviderField::Kind,
+        ProviderField::Model,
+        ProviderField::ApiKey,
+        ProviderField::BaseUrl,
+    ];
+
+    fn label(self) -> &'static str {
+        match self {
+            ProviderField::Name => "name",
+            ProviderField::Kind => "provider",
+            ProviderField::Model => "model",
+            ProviderField::ApiKey => "api key",
+            ProviderField::BaseUrl => "base url",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum FormStatus {
+    #[default]
+    Loading,
+    Ready,
+    Saving,
+    Saved,
+    Failed(String),
+}
+
+/// The model config being edited, and where the user is in the form.
+#[derive(Debug, Default)]
+pub struct ModelFormState {
+    /// The loaded config, its providers are edited in `providers`.
+    config: Option<ModelConfig>,
+    /// In the order they are shown, so renaming one does not move it.
+    providers: Vec<(String, ProviderConfig)>,
+    cursor: usize,
+    scroll: u16,
+    /// The text field being edited.
+    editor: Option<TextArea<'static>>,
+    /// Why the last save was refused.
+    errors: Vec<ConfigError>,
+    pub status: FormStatus,
+}
+
+impl ModelFormState {
+    /// Forget the config, waiting for it to be loaded again.
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+
+    pub fn load(&mut self, mut config: ModelConfig) {
+        self.providers = std::mem::take(&mut config.configs).into_iter().collect();
+        self.config = Some(config);
+        self.cursor = 0;
+        self.scroll = 0;
+        self.editor = None;
+        self.errors.clear();
+        self.status = FormStatus::Ready;
+    }
+
+    /// The form differs from what was last saved.
+    fn touch(&mut self) {
+        if self.status == FormStatus::Saved {
+            self.status = FormStatus::Ready;
+        }
+    }
+
+    #[inline]
+    pub fn editing(&self) -> bool {
+        self.editor.is_some()
+    }
+
+    fn rows(&self) -> Vec<Row> {
+        if self.config.is_none() {
+            return vec![];
+        }
+
+        let mut rows = vec![Row::ActiveProvider, Row::EnableTools, Row::DisableSystemPrompt];
+        for i in 0..self.providers.len() {
+            rows.extend(ProviderField::ALL.map(|field| Row::Provider(i, field)));
+        }
+        rows.push(Row::AddProvider);
+        rows
+    }
+
+    fn row(&self) -> Option<Row> {
+        self.rows().get(self.cursor).copied()
+    }
+
+    pub fn move_cursor(&mut self, offset: isize) {
+        let len = self.rows().len();
+        self.cursor = self.cursor.saturating_add_signed(offset).min(len.saturating_sub(1));
+    }
+
+    /// Toggle a switch, step a choice or start editing a text field.
+    pub fn activate(&mut self) {
+        match self.row() {
+            Some(Row::EnableTools) => {
+                let config = self.config.as_mut().unwrap();
+                config.enable_tools = !config.enable_tools;
+                self.touch();
+            }
+            Some(Row::DisableSystemPrompt) => {
+                let config = self.config.as_mut().unwrap();
+                config.disable_dive_system_prompt = !config.disable_dive_system_prompt;
+                self.touch();
+            }
+            Some(Row::ActiveProvider) | Some(Row::Provider(_, ProviderField::Kind)) => self.cycle(1),
+            Some(Row::Provider(i, field)) => {
+                let (name, provider) = &self.providers[i];
+                let value = match field {
+                    ProviderField::Name => name.clone(),
+                    ProviderField::Model => provider.model.clone(),
+                    ProviderField::ApiKey => provider.api_key.clone().unwrap_or_default(),
+                    ProviderField::BaseUrl => provider.base_url().unwrap_or_default().to_string(),
+                    ProviderField::Kind => unreachable!(),
+                };
+
+                let mut editor = TextArea::new(vec![value]);
+                editor.move_cursor(tui_textarea::CursorMove::End);
+                editor.set_cursor_line_style(Style::default());
+                if field == ProviderField::ApiKey {
+                    editor.set_mask_char('•');
+                }
+                self.editor = Some(editor);
+            }
+            Some(Row::AddProvider) => self.add_provider(),
+            None => {}
+        }
+    }
+
+    /// Step the choice under the cursor forwards or backwards.
+    pub fn cycle(&mut self, offset: isize) {
+        let step = |choices: &[&str], current: &str| {
+            let len = choices.len() as isize;
+            let at = choices.iter().position(|c| *c == current).map_or(-1, |i| i as isize);
+            let next = if at < 0 && offset < 0 { len - 1 } else { (at + offset).rem_euclid(len) };
+            choices[next as usize].to_string()
+        };
+
+        match self.row() {
+            Some(Row::ActiveProvider) => {
+                let mut choices = vec![FAKE_PROVIDER];
+                choices.extend(self.providers.iter().map(|(name, _)| name.as_str()));
+                let config = self.config.as_ref().unwrap();
+                let next = step(&choices, &config.active_provider);
+                self.config.as_mut().unwrap().active_provider = next;
+            }
+            Some(Row::Provider(i, ProviderField::Kind)) => {
+                let provider = &mut self.providers[i].1;
+                provider.model_provider = step(MODEL_PROVIDERS, &provider.model_provider);
+            }
+            _ => return,
+        }
+        self.touch();
+    }
+
+    pub fn add_provider(&mut self) {
+        if self.config.is_none() {
+            return;
+        }
+
+        let name = (1..)
+            .map(|n| format!("provider-{}", n))
+            .find(|name| self.providers.iter().all(|(other, _)| other != name))
+            .unwrap();
+        let provider = ProviderConfig {
+            model_provider: MODEL_PROVIDERS[0].to_string(),
+            ..Default::default()
+        };
+        self.providers.push((name, provider));
+        self.touch();
+
+        let i = self.providers.len() - 1;
+        self.cursor = self.rows().iter().position(|row| *row == Row::Provider(i, ProviderField::Name)).unwrap_or(0);
+    }
+
+    /// Drop the provider under the cursor.
+    pub fn remove_provider(&mut self) {
+        let Some(Row::Provider(i, _)) = self.row() else {
+            return;
+        };
+
+        self.providers.remove(i);
+        self.touch();
+        self.move_cursor(0);
+    }
+
+    /// Edit the text field being edited, committing it on enter.
+    pub fn input(&mut self, event: KeyEvent) {
+        let Some(editor) = self.editor.as_mut() else {
+            return;
+        };
+
+        match event.code {
+            KeyCode::Esc => self.editor = None,
+            KeyCode::Enter => {
+                let value = editor.lines().join("").trim().to_string();
+                self.editor = None;
+                self.set_field(value);
+            }
+            _ => {
+                editor.input(event);
+            }
+        }
+    }
+
+    fn set_field(&mut self, value: String) {
+        let Some(Row::Provider(i, field)) = self.row() else {
+            return;
+        };
+
+        let (name, provider) = &mut self.providers[i];
+        match field {
+            ProviderField::Name => {
+                // keep the provider active under its new name
+                let config = self.config.as_mut().unwrap();
+                if config.active_provider == *name {
+                    config.active_provider = value.clone();
+                }
+                *name = value;
+            }
+            ProviderField::Model => provider.model = value,
+            ProviderField::ApiKey => provider.api_key = (!value.is_empty()).then_some(value),
+            ProviderField::BaseUrl => provider.set_base_url((!value.is_empty()).then_some(value)),
+            ProviderField::Kind => {}
+        }
+        self.touch();
+    }
+
+    /// The edited config if it is valid, otherwise keep why it is not.
+    pub fn submit(&mut self) -> Option<ModelConfig> {
+        let mut config = self.config.clone()?;
+        self.errors.clear();
+        for (name, provider) in &self.providers {
+            if config.configs.insert(name.clone(), provider.clone()).is_some() {
+                self.errors.push(ConfigError {
+                    provider: Some(name.clone()),
+                    message: format!("another provider is named \"{}\"", name),
+                });
+            }
+        }
+        self.errors.extend(config.validate());
+
+        if !self.errors.is_empty() {
+            self.status = FormStatus::Ready;
+            return None;
+        }
+
+        self.status = FormStatus::Saving;
+        Some(config)
+    }
+}
+
+pub struct ModelForm;
+
+impl StatefulWidget for ModelForm {
+    type State = ModelFormState;
+
+    fn render(self, area: Rect, buf: &mut Buffer, state: &mut Self::State) {
+        let area = centered_rect(area, 80, 80);
+        Clear.render(area, buf);
+
+        let status = match &state.status {
+            FormStatus::Loading => " loading…".dark_gray(),
+            FormStatus::Ready => "".into(),
+            FormStatus::Saving => " saving…".dark_gray(),
+            FormStatus::Saved => " saved".green(),
+            FormStatus::Failed(_) => " failed".red(),
+        };
+        let block = Block::default()
+            .borders(Borders::ALL)
+            .border_type(BorderType::Rounded)
+            .border_style(Style::default().fg(Color::Blue))
+            .title(Line::from(vec!["Model config".into(), status]));
+        let inner = block.inner(area);
+        block.render(area, buf);
+
+        let Some(config) = &state.config else {
+            let hint = match &state.status {
+                FormStatus::Failed(error) => Line::from(error.as_str().red()),
+                _ => Line::from("loading the model config from the host…".dark_gray()),
+            };
+            Paragraph::new(hint).render(inner, buf);
+            return;
+        };
+
+        let errors_of = |provider: Option<&str>| -> Vec<Line<'static>> {
+            state.errors.iter()
+                .filter(|error| error.provider.as_deref() == provider)
+                .map(|error| Line::from(format!("  ! {}", error.message).red()))
+                .collect()
+        };
+
+        // the line of every row, in the order of `rows`
+        let mut at = vec![];
+        let mut lines = vec![];
+        let mut push_row = |lines: &mut Vec<Line<'static>>, label: &str, value: Span<'static>| {
+            let mut line = Line::from(vec![
+                Span::raw(format!("    {:<width$}", label, width = LABEL_WIDTH as usize - 4)),
+                value,
+            ]);
+            if at.len() == state.cursor {
+                line = line.style(Style::default().add_modifier(Modifier::REVERSED));
+            }
+            at.push(lines.len());
+            lines.push(line);
+        };
+
+        lines.push(Line::from("General".bold()));
+        push_row(&mut lines, "active provider", choice(&config.active_provider));
+        push_row(&mut lines, "enable tools", switch(config.enable_tools));
+        push_row(&mut lines, "disable dive system prompt", switch(config.disable_dive_system_prompt));
+        lines.extend(errors_of(None));
+        lines.push(Line::default());
+
+        lines.push(Line::from("Providers".bold()));
+        for (name, provider) in &state.providers {
+            let active = if *name == config.active_provider { " (active)".green() } else { "".into() };
+            lines.push(Line::from(vec![format!("  {}", name).cyan(), active]));
+            for field in ProviderField::ALL {
+                let value = match field {
+                    ProviderField::Name => text(name),
+                    ProviderField::Kind => choice(&provider.model_provider),
+                    ProviderField::Model => text(&provider.model),
+                    ProviderField::ApiKey => provider.api_key.as_deref().map_or_else(|| "-".dark_gray(), mask),
+                    ProviderField::BaseUrl => provider.base_url().map_or_else(|| "-".dark_gray(), text),
+                };
+                push_row(&mut lines, field.label(), value);
+            }
+            lines.extend(errors_of(Some(name)));
+            lines.push(Line::default());
+        }
+        push_row(&mut lines, "", "+ add provider".into());
+        if let FormStatus::Failed(error) = &state.status {
+            lines.push(Line::default());
+            lines.push(Line::from(format!("! {}", error).red()));
+        }
+
+        // keep the cursor in view
+        let cursor_line = at.get(state.cursor).copied().unwrap_or_default() as u16;
+        if cursor_line < state.scroll {
+            state.scroll = cursor_line.saturating_sub(1);
+        } else if cursor_line >= state.scroll + inner.height {
+            state.scroll = cursor_line + 1 - inner.height.max(1);
+        }
+        Paragraph::new(lines)
+            .scroll((state.scroll, 0))
+            .render(inner, buf);
+
+        if let Some(editor) = &state.editor {
+            let y = inner.y + cursor_line - state.scroll;
+            if y < inner.bottom() && inner.width > LABEL_WIDTH {
+                let field = Rect { x: inner.x + LABEL_WIDTH, y, width: inner.width - LABEL_WIDTH, height: 1 };
+                Clear.render(field, buf);
+                editor.render(field, buf);
+            }
+        }
+    }
+}
+
+fn text(value: &str) -> Span<'static> {
+    if value.is_empty() { "-".dark_gray() } else { value.to_string().into() }
+}
+
+fn choice(value: &str) -> Span<'static> {
+    format!("< {} >", value).into()
+}
+
+fn switch(on: bool) -> Span<'static> {
+    if on { "[x]".green() } else { "[ ]".into() }
+}
+
+/// Show only the end of a secret, enough to tell keys apart.
+fn mask(secret: &str) -> Span<'static> {
+    let tail: String = secret.chars().rev().take(4).collect::<Vec<_>>().into_iter().rev().collect();
+    let hidden = secret.chars().count().saturating_sub(4).min(12);
+    format!("{}{}", "•".repeat(hidden), tail).into()
+}
@@ -0,0 +1,211 @@
+use fuzzy_matcher::{skim::SkimMatcherV2, FuzzyMatcher};
+use ratatui::{
+    buffer::Buffer,
+    layout::{Constraint, Layout, Rect},
+    style::{Color, Style, Stylize},
+    text::{Line, Span},
+    widgets::{
+        Block,
+        BorderType,
+        Borders,
+        Clear,
+        List,
+        ListItem,
+        ListState,
+        StatefulWidget,
+        Widget,
+    },
+};
+
+use crate::model_config::{ModelConfig, FAKE_PROVIDER};
+
+use super::{centered_rect, model_form::FormStatus};
+
+/// A provider and one of its models the chats can be switched to.
+#[derive(Debug, Clone)]
+struct ModelChoice {
+    provider: String,
+    model: String,
+    /// What is matched against the query, the provider name then its model.
+    label: String,
+}
+
+/// The configured models filtered by the query typed in the switcher.
+#[derive(Debug, Default)]
+pub struct ModelSwitcherState {
+    config: Option<ModelConfig>,
+    choices: Vec<ModelChoice>,
+    pub query: String,
+    /// Choices matching the query, best first, with the matched characters
+    /// of their label.
+    matches: Vec<(usize, Vec<usize>)>,
+    pub list: ListState,
+    pub status: FormStatus,
+}
+
+impl ModelSwitcherState {
+    /// Forget the config, waiting for it to be loaded again.
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+
+    /// Offer the model configured for each provider, until the providers
+    /// list the others with `add_models`.
+    pub fn load(&mut self, config: ModelConfig) {
+        self.choices = config.configs.iter()
+            .map(|(name, provider)| ModelChoice {
+                provider: name.clone(),
+                model: provider.model.clone(),
+                label: format!("{}  {} ({})", name, provider.model, provider.model_provider),
+            })
+            .collect();
+        if !config.configs.contains_key(FAKE_PROVIDER) {
+            self.choices.push(ModelChoice {
+                provider: FAKE_PROVIDER.to_string(),
+                model: String::new(),
+                label: format!("{}  no model, for testing", FAKE_PROVIDER),
+            });
+        }
+        self.config = Some(config);
+        self.status = FormStatus::Ready;
+        self.update();
+    }
+
+    /// Offer the other models `provider` lists, keeping the selection.
+    pub fn add_models(&mut self, provider: &str, models: Vec<String>) {
+        let Some(config) = self.config.as_ref().and_then(|config| config.configs.get(provider)) else {
+            return;
+        };
+
+        let known = |choices: &[ModelChoice], model: &str| {
+            choices.iter().any(|choice| choice.provider == provider && choice.model == model)
+        };
+        // keep the models of a provider together, after the configured one
+        let mut at = self.choices.iter()
+            .rposition(|choice| choice.provider == provider)
+            .map_or(self.choices.len(), |i| i + 1);
+        for model in models {
+            if !known(&self.choices, &model) {
+                self.choices.insert(at, ModelChoice {
+                    provider: provider.to_string(),
+                    label: format!("{}  {} ({})", provider, model, config.model_provider),
+                    model,
+                });
+                at += 1;
+            }
+        }
+
+        let selected = self.selected();
+        self.update();
+        if let Some(selected) = selected {
+            let at = self.matches.iter().position(|(i, _)| *i == selected);
+            self.list.select(at.or(self.list.selected()));
+        }
+    }
+
+    fn selected(&self) -> Option<usize> {
+        self.matches.get(self.list.selected()?).map(|(i, _)| *i)
+    }
+
+    fn is_active(&self, choice: &ModelChoice) -> bool {
+        self.config.as_ref().is_some_and(|config| {
+            config.active_provider == choice.provider
+                && config.configs.get(&choice.provider).is_none_or(|provider| provider.model == choice.model)
+        })
+    }
+
+    /// Match the choices against the query again.
+    pub fn update(&mut self) {
+        let matcher = SkimMatcherV2::default().smart_case();
+        let mut matches: Vec<_> = self.choices.iter()
+            .enumerate()
+            .filter_map(|(i, choice)| {
+                let (score, indices) = matcher.fuzzy_indices(&choice.label, &self.query)?;
+                Some((score, i, indices))
+            })
+            .collect();
+        matches.sort_by_key(|(score, _, _)| std::cmp::Reverse(*score));
+        self.matches = matches.into_iter().map(|(_, i, indices)| (i, indices)).collect();
+
+        // start on the active model while nothing is typed
+        let at = self.query.is_empty()
+            .then(|| self.matches.iter().position(|(i, _)| self.is_active(&self.choices[*i])))
+            .flatten();
+        self.list.select(if self.matches.is_empty() { None } else { Some(at.unwrap_or(0)) });
+    }
+
+    /// The config with the selected model active, `None` if it already is.
+    pub fn switch(&mut self) -> Option<ModelConfig> {
+        let choice = &self.choices[self.selected()?];
+        if self.is_active(choice) {
+            return None;
+        }
+
+        let mut config = self.config.clone()?;
+        config.active_provider = choice.provider.clone();
+        if let Some(provider) = config.configs.get_mut(&choice.provider) {
+            provider.model = choice.model.clone();
+        }
+        self.status = FormStatus::Saving;
+        Some(config)
+    }
+}
+
+pub struct ModelSwitcher;
+
+impl StatefulWidget for ModelSwitcher {
+    type State = ModelSwitcherState;
+
+    fn render(self, area: Rect, buf: &mut Buffer, state: &mut Self::State) {
+        let area = centered_rect(area, 60, 50);
+        Clear.render(area, buf);
+
+        let status = match &state.status {
+            FormStatus::Loading => " loading…".dark_gray(),
+            FormStatus::Saving => " switching…".dark_gray(),
+            FormStatus::Failed(_) => " failed".red(),
+            FormStatus::Ready | FormStatus::Saved => "".into(),
+        };
+        let block = Block::default()
+            .borders(Borders::ALL)
+            .border_type(BorderType::Rounded)
+            .border_style(Style::default().fg(Color::Blue))
+            .title(Line::from(vec!["Switch model".into(), status]));
+        let inner = block.inner(area);
+        block.render(area, buf);
+
+        let [prompt, results, error] = Layout::vertical([
+            Constraint::Length(2),
+            Constraint::Min(0),
+            Constraint::Length(if matches!(state.status, FormStatus::Failed(_)) { 1 } else { 0 }),
+        ])
+        .areas(inner);
+        Line::from(vec!["> ".cyan(), format!("{}▏", state.query).into()]).render(prompt, buf);
+        if let FormStatus::Failed(message) = &state.status {
+            Line::from(message.as_str().red()).render(error, buf);
+        }
+
+        if state.config.is_none() {
+            return;
+        }
+        if state.matches.is_empty() {
+            Line::from("no matching model".dark_gray()).render(results, buf);
+            return;
+        }
+
+        let items = state.matches.iter().map(|(i, indices)| {
+            let choice = &state.choices[*i];
+            let active = state.is_active(choice);
+            let mut spans = vec![if active { "● ".green() } else { "  ".into() }];
+            for (n, c) in choice.label.chars().enumerate() {
+                let span = Span::raw(c.to_string());
+                spans.push(if indices.contains(&n) { span.yellow().bold() } else { span });
+            }
+            ListItem::new(Line::from(spans))
+        });
+
+        let list = List::new(items)
+            .highlight_style(Style::default().bg(Color::DarkGray));
+        StatefulWidget::render(list, results, buf, &mut state.list);
+    }
+}
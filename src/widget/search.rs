@@ -0,0 +1,64 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use ratatui::buffer::Buffer;
+use regex::{Regex, RegexBuilder};
+use unicode_width::UnicodeWidthStr;
+
+static NEXT_SEARCH_ID: AtomicU64 = AtomicU64::new(0);
+
+/// How the query of a search is read.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SearchOptions {
+    /// `?` searches towards the start of the chat.
+    pub backward: bool,
+    /// The query is a regular expression rather than plain text.
+    pub regex: bool,
+    /// Match case even for a query without uppercase letters.
+    pub match_case: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct Search {
+    regex: Regex,
+    pub backward: bool,
+    /// Different for every search, so matches found for another are dropped.
+    pub id: u64,
+}
+
+impl Search {
+    /// Compile `query`, ignoring case unless asked not to or the query has an
+    /// uppercase letter.
+    pub fn new(query: &str, options: SearchOptions) -> Result<Self, regex::Error> {
+        let pattern = if options.regex { query.to_string() } else { regex::escape(query) };
+        let ignore_case = !options.match_case && !query.chars().any(char::is_uppercase);
+        let regex = RegexBuilder::new(&pattern).case_insensitive(ignore_case).build()?;
+
+        Ok(Self {
+            regex,
+            backward: options.backward,
+            id: NEXT_SEARCH_ID.fetch_add(1, Ordering::Relaxed),
+        })
+    }
+
+    /// The columns of every match on the row `y` of `buf`, end excluded.
+    ///
+    /// Lines are searched as rendered, a match cannot span a wrapped line.
+    pub fn find_in_row(&self, buf: &Buffer, y: u16) -> Vec<(u16, u16)> {
+        let mut text = String::new();
+        // the column of every byte of the text
+        let mut columns = vec![];
+        let mut x = 0;
+        while let Some(cell) = buf.cell((x, y)) {
+            let symbol = cell.symbol();
+            text.push_str(symbol);
+            columns.extend(std::iter::repeat_n(x, symbol.len()));
+            x = x.saturating_add(symbol.width().max(1) as u16);
+        }
+        columns.push(x);
+
+        self.regex.find_iter(&text)
+            .filter(|m| !m.is_empty())
+            .map(|m| (columns[m.start()], columns[m.end()]))
+            .collect()
+    }
+}
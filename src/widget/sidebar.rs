@@ -0,0 +1,51 @@
+use ratatui::{
+    buffer::Buffer,
+    layout::Rect,
+    style::{Color, Style, Stylize},
+    text::Line,
+    widgets::{
+        Block,
+        BorderType,
+        Borders,
+        List,
+        ListItem,
+        ListState,
+        StatefulWidget,
+        Widget,
+    },
+};
+
+use crate::chat::{ChatSummary, ThreadKey};
+
+pub struct Sidebar<'a> {
+    pub chats: &'a [ChatSummary],
+    pub active: ThreadKey,
+    pub streaming: Option<ThreadKey>,
+}
+
+impl Widget for Sidebar<'_> {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let block = Block::default()
+            .borders(Borders::ALL)
+            .border_type(BorderType::Rounded)
+            .title("Chats");
+
+        let items = self.chats.iter().map(|chat| {
+            let marker = if self.streaming == Some(chat.key) { "● " } else { "  " };
+            let title = match &chat.title {
+                Some(title) => title.as_str().into(),
+                None => "New chat".dark_gray(),
+            };
+
+            ListItem::new(Line::from(vec![marker.green(), title]))
+        });
+
+        let mut state = ListState::default()
+            .with_selected(self.chats.iter().position(|chat| chat.key == self.active));
+
+        let list = List::new(items)
+            .block(block)
+            .highlight_style(Style::default().fg(Color::Black).bg(Color::Blue));
+        StatefulWidget::render(list, area, buf, &mut state);
+    }
+}
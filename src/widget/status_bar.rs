@@ -1,40 +1,96 @@
 use ratatui::{
-    buffer::Buffer, layout::Rect, widgets::{
+    buffer::Buffer, layout::{Constraint, Layout, Rect}, style::{Color, Style}, text::{Line, Span}, widgets::{
         Block, Borders, Paragraph, Widget, WidgetRef, Wrap
     }
 };
 
-use crate::tui::InputMode;
+use crate::{supervisor::HostHealth, tui::InputMode};
 
-pub struct StatusBar {
+pub struct StatusBar<'a> {
     pub mode: InputMode,
+    /// What the user is typing in a prompt.
+    pub prompt: Option<&'a str>,
+    /// A failure shown until the next key.
+    pub notice: Option<&'a str>,
+    pub streaming: bool,
+    /// The input replaces an earlier user message.
+    pub editing: bool,
+    /// The model answering the next message.
+    pub model: Option<&'a str>,
+    pub host: &'a HostHealth,
 }
 
-impl StatusBar {
+impl StatusBar<'_> {
     #[inline]
     pub fn content(&self) -> &str {
         match self.mode {
-            InputMode::Normal => "[q] quit | [i, a] chat",
+            InputMode::Normal if self.streaming => "[q] quit | [ctrl-c] abort | [j, k, ctrl-d/u/f/b, gg, G, {, }] scroll | [/, ?] search | [n, N] next/prev match | [ctrl-p, ctrl-n] prev/next turn | [t] toggle tools | [N]y yank code | [[, ]] switch chat",
+            InputMode::Normal => "[q] quit | [i, a] chat | [j, k, ctrl-d/u/f/b, gg, G, {, }] scroll | [/, ?] search | [n, N] next/prev match | [ctrl-p, ctrl-n] prev/next turn | [e] edit turn | [r] retry | [<, >] generation | [t] toggle tools | [N]y yank code | [h, l] scroll code | [[, ]] switch chat",
+            InputMode::Insert if self.editing => "[esc] cancel edit | [enter] send edit",
             InputMode::Insert => "[esc] normal | [enter] send",
-            InputMode::Leader => "[esc] normal | [e] edit file",
-            InputMode::EditFile => "[esc] normal | [m] edit model config | [s] edit mcp config",
+            InputMode::Leader => "[esc] normal | [e] edit file | [h] history | [n] new chat | [b] chat list | [m] switch model | [t] mcp servers | [/] search all chats | [d] host diagnostics | [l] logs",
+            InputMode::EditFile => "[esc] normal | [m] model config | [M] model config in $EDITOR | [s] edit mcp config",
+            InputMode::ModelConfig => "[esc] close | [j, k] move | [enter, space] edit/toggle | [h, l] change choice | [a] add provider | [d] delete provider | [s] save",
+            InputMode::History => "[esc] close | [j, k] move | [enter] open chat",
+            InputMode::Diagnostics => "[esc] close",
+            InputMode::Logs => "[esc] close | [tab] host/tive log | [j, k] scroll | [g, G] top/bottom | [f] follow | [/] search | [v] level",
+            InputMode::ModelSwitcher => "[esc] close | [up, down] move | [enter] use model",
+            InputMode::Mcp => "[esc] close | [j, k] move | [h, l] servers/tools | [space] enable/disable | [a] add server | [r] reload",
+            InputMode::ChatSearch => "[esc] close | [up, down] move | [enter] open chat",
+            InputMode::Search => "[esc] cancel | [enter] search | [alt-r] regex | [alt-c] match case",
         }
     }
 }
 
-impl WidgetRef for StatusBar {
+impl WidgetRef for StatusBar<'_> {
     fn render_ref(&self, area: Rect, buf: &mut Buffer) {
         let block = Block::default()
             .borders(Borders::TOP);
+        let inner = block.inner(area);
+        block.render(area, buf);
 
-        Paragraph::new(self.content())
-            .block(block)
+        let (host, color) = match self.host {
+            HostHealth::Connecting => ("connecting…".to_string(), Color::DarkGray),
+            HostHealth::Up { endpoint, error: None } => (endpoint.clone(), Color::DarkGray),
+            HostHealth::Up { endpoint, error: Some(error) } => (format!("{} ! {}", endpoint, error), Color::Yellow),
+            HostHealth::Restarting { attempt, error } => (format!("host down, restart #{}: {}", attempt, error), Color::Red),
+        };
+
+        let mut status = Line::default();
+        if let Some(model) = self.model {
+            status.push_span(Span::styled(model, Style::default().fg(Color::Cyan)));
+            status.push_span(Span::styled(" | ", Style::default().fg(Color::DarkGray)));
+        }
+        status.push_span(Span::styled(host, Style::default().fg(color)));
+
+        // keep at least half of the bar for the key hints
+        let host_width = (status.width() as u16 + 1).min(inner.width / 2);
+        let [hints, host_area] = Layout::horizontal([
+            Constraint::Min(1),
+            Constraint::Length(host_width),
+        ])
+        .areas(inner);
+
+        let mut line = Line::default();
+        if let Some(notice) = self.notice {
+            line.push_span(Span::styled(notice, Style::default().fg(Color::Red)));
+            line.push_span("  ");
+        }
+        if let Some(prompt) = self.prompt {
+            line.push_span(Span::styled(prompt, Style::default().fg(Color::Cyan)));
+            line.push_span("  ");
+        }
+        line.push_span(self.content());
+        Paragraph::new(line)
             .wrap(Wrap { trim: true })
-            .render(area, buf);
+            .render(hints, buf);
+        Paragraph::new(status)
+            .right_aligned()
+            .render(host_area, buf);
     }
 }
 
-impl Widget for StatusBar {
+impl Widget for StatusBar<'_> {
     fn render(self, area: Rect, buf: &mut Buffer) {
         self.render_ref(area, buf);
     }